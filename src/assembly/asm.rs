@@ -1,4 +1,5 @@
 use crate::instruction;
+use crate::parser::E2;
 
 #[derive(Debug, Eq, PartialEq)]
 pub enum AsmInst {
@@ -55,3 +56,11 @@ pub enum Calc {
 
 #[derive(Debug, Eq, PartialEq)]
 pub struct Label(pub String);
+
+// a statement with its 1-based position in the source
+#[derive(Debug, Eq, PartialEq)]
+pub struct Line {
+    pub number: usize,
+    pub column: usize,
+    pub statement: E2<AsmInst, Label>,
+}
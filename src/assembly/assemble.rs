@@ -6,39 +6,95 @@ use crate::instruction::Instruction;
 use crate::parser::E2;
 
 use super::asm::*;
+use super::error::{AsmError, AsmErrorKind};
+use super::source_map::SourceMap;
 
-pub fn assemble(code: &str) -> Result<Vec<Bus16>, String> {
-    let lines = parse(code)?;
-    let mut t = label_table(&lines)?;
+pub fn assemble(code: &str) -> Result<Vec<Bus16>, Vec<AsmError>> {
+    assemble_with_source_map(code).map(|(bin, _)| bin)
+}
+
+pub fn assemble_with_source_map(code: &str) -> Result<(Vec<Bus16>, SourceMap), Vec<AsmError>> {
+    let (lines, mut errors) = parse(code);
+    let mut t = match label_table(&lines) {
+        Ok(t) => t,
+        Err(e) => {
+            errors.extend(e);
+            HashMap::new()
+        }
+    };
+    if !errors.is_empty() {
+        errors.sort_by_key(|e| (e.line, e.column));
+        return Err(errors);
+    }
     t.extend(var_table(&lines, &t));
-    let main = resolve(lines, &t).iter().map(|inst| inst.bus16()).collect();
-    Ok(main)
+    let source_map = SourceMap::of(code, &lines);
+    let main = resolve(lines, &t)?
+        .iter()
+        .map(|inst| inst.bus16())
+        .collect();
+    Ok((main, source_map))
 }
 
-fn label_table(lines: &Vec<E2<AsmInst, Label>>) -> Result<HashMap<String, i32>, String> {
-    let mut table = HashMap::new();
+pub(crate) const PREDEFINED_SYMBOLS: [(&str, i32); 23] = [
+    ("SP", 0x0000),
+    ("LCL", 0x0001),
+    ("ARG", 0x0002),
+    ("THIS", 0x0003),
+    ("THAT", 0x0004),
+    ("R0", 0x0000),
+    ("R1", 0x0001),
+    ("R2", 0x0002),
+    ("R3", 0x0003),
+    ("R4", 0x0004),
+    ("R5", 0x0005),
+    ("R6", 0x0006),
+    ("R7", 0x0007),
+    ("R8", 0x0008),
+    ("R9", 0x0009),
+    ("R10", 0x000a),
+    ("R11", 0x000b),
+    ("R12", 0x000c),
+    ("R13", 0x000d),
+    ("R14", 0x000e),
+    ("R15", 0x000f),
+    ("SCREEN", 0x4000),
+    ("KBD", 0x6000),
+];
+
+fn label_table(lines: &Vec<Line>) -> Result<HashMap<String, i32>, Vec<AsmError>> {
+    let mut table: HashMap<String, i32> = PREDEFINED_SYMBOLS
+        .iter()
+        .map(|&(s, addr)| (String::from(s), addr))
+        .collect();
+    let mut errors = Vec::new();
     let mut pc = 0;
     for line in lines {
-        match line {
+        match &line.statement {
             E2::A(_) => pc += 1,
             E2::B(Label(label)) => {
-                table
-                    .try_insert(label.clone(), pc)
-                    .map_err(|e| e.entry.key().clone())?;
+                if table.try_insert(label.clone(), pc).is_err() {
+                    errors.push(AsmError {
+                        line: line.number,
+                        column: line.column + 1,
+                        text: label.clone(),
+                        kind: AsmErrorKind::DuplicateLabel,
+                    });
+                }
             }
         }
     }
-    Ok(table)
+    if errors.is_empty() {
+        Ok(table)
+    } else {
+        Err(errors)
+    }
 }
 
-fn var_table(
-    lines: &Vec<E2<AsmInst, Label>>,
-    labels: &HashMap<String, i32>,
-) -> HashMap<String, i32> {
+fn var_table(lines: &Vec<Line>, labels: &HashMap<String, i32>) -> HashMap<String, i32> {
     let mut table = HashMap::new();
     let mut addr = 0x10;
     for line in lines {
-        if let E2::A(AsmInst::A(A::Var(a))) = line {
+        if let E2::A(AsmInst::A(A::Var(a))) = &line.statement {
             if labels.contains_key(a) {
                 continue;
             }
@@ -51,24 +107,46 @@ fn var_table(
     table
 }
 
-fn resolve(mut lines: Vec<E2<AsmInst, Label>>, table: &HashMap<String, i32>) -> Vec<Instruction> {
-    lines
-        .drain(..)
-        .filter_map(|e| match e {
-            E2::A(a) => match a {
-                AsmInst::A(A::Const(c)) => Some(Instruction::A(c)),
-                AsmInst::A(A::Var(v)) => table.get(&v).map(|&x| Instruction::A(x)),
-                AsmInst::C(c) => Some(Instruction::C(c)),
+// symbols can still resolve out of range, e.g. a label past the 32K ROM
+fn resolve(
+    mut lines: Vec<Line>,
+    table: &HashMap<String, i32>,
+) -> Result<Vec<Instruction>, Vec<AsmError>> {
+    let mut insts = Vec::new();
+    let mut errors = Vec::new();
+    for l in lines.drain(..) {
+        let (value, text) = match l.statement {
+            E2::A(AsmInst::A(A::Const(c))) => (c, c.to_string()),
+            E2::A(AsmInst::A(A::Var(v))) => match table.get(&v) {
+                Some(&x) => (x, v),
+                None => continue,
             },
-            _ => None,
-        })
-        .collect()
+            E2::A(AsmInst::C(c)) => {
+                insts.push(Instruction::C(c));
+                continue;
+            }
+            E2::B(_) => continue,
+        };
+        match Instruction::a(value) {
+            Some(inst) => insts.push(inst),
+            None => errors.push(AsmError {
+                line: l.number,
+                column: l.column + 1,
+                text,
+                kind: AsmErrorKind::ConstantOutOfRange,
+            }),
+        }
+    }
+    if errors.is_empty() {
+        Ok(insts)
+    } else {
+        Err(errors)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::computer::testing::ComputerDebug;
-    use crate::computer::MutComputer;
+    use crate::computer::{Computer, HaltReason, MutComputer};
     use crate::infrastructure::sequential::MutSC;
 
     use super::*;
@@ -88,10 +166,8 @@ mod tests {
 
         let bin = assemble(&code).unwrap();
 
-        let mut c = MutComputer::of(&bin);
-        for _ in 0..10 {
-            c.tick(&())
-        }
+        let mut c = MutComputer::of(&bin).unwrap();
+        assert_eq!(c.run_until_halt(100).1, HaltReason::Halted);
 
         assert_eq!(c.peek_ram(0), 5)
     }
@@ -143,12 +219,154 @@ mod tests {
         for &(x, y, ans) in test_cases.iter() {
             let bin = assemble(&(prepare(x, y) + code)).unwrap();
 
-            let mut c = MutComputer::of(&bin);
-            for _ in 0..bin.len() {
-                c.tick(&())
-            }
+            let mut c = MutComputer::of(&bin).unwrap();
+            assert_eq!(c.run_until_halt(100).1, HaltReason::Halted);
 
             assert_eq!(c.peek_ram(2), ans)
         }
     }
+
+    #[test]
+    fn predefined_symbols_work() {
+        let code = "
+        @R1
+        D=A
+        @SCREEN
+        D=D+A
+        @KBD
+        D=D+A
+        @THAT
+        D=D+A
+        @i
+        M=D
+        @j
+        M=1\n";
+
+        let (lines, _) = parse(code);
+        let mut t = label_table(&lines).unwrap();
+        t.extend(var_table(&lines, &t));
+        assert_eq!(t.get("R1"), Some(&1));
+        assert_eq!(t.get("SCREEN"), Some(&0x4000));
+        assert_eq!(t.get("KBD"), Some(&0x6000));
+        assert_eq!(t.get("THAT"), Some(&4));
+        assert_eq!(t.get("i"), Some(&0x10));
+        assert_eq!(t.get("j"), Some(&0x11));
+
+        let bin = assemble(code).unwrap();
+        let mut c = MutComputer::of(&bin).unwrap();
+        for _ in 0..bin.len() {
+            c.tick(&())
+        }
+        assert_eq!(c.peek_ram(0x10) as u16, 1 + 0x4000 + 0x6000 + 4);
+        assert_eq!(c.peek_ram(0x11), 1);
+    }
+
+    #[test]
+    fn assemble_reports_every_error() {
+        let code = "
+        (LOOP)
+        D=D+Q
+        (LOOP)
+        @LOOP
+        0;JMP
+        (END)
+        (END)\n";
+        let errors = assemble(code).unwrap_err();
+        assert_eq!(
+            errors.iter().map(|e| (e.line, e.kind)).collect::<Vec<_>>(),
+            vec![
+                (3, AsmErrorKind::UnknownComp),
+                (4, AsmErrorKind::DuplicateLabel),
+                (8, AsmErrorKind::DuplicateLabel),
+            ]
+        );
+    }
+
+    #[test]
+    fn source_map_works() {
+        let code = "// sum
+        @i
+        M=1 // i = 1
+        (LOOP)
+        @i
+        D=M
+
+        @LOOP
+        D;JGT
+        (END)
+        @END
+        0;JMP\n";
+        let (bin, map) = assemble_with_source_map(code).unwrap();
+        assert_eq!(map.len(), bin.len());
+
+        let loc = map.get(0).unwrap();
+        assert_eq!((loc.line, loc.text.as_str(), &loc.label), (2, "@i", &None));
+        let loc = map.get(1).unwrap();
+        assert_eq!(loc.to_string(), "line 3: M=1");
+        let loc = map.get(2).unwrap();
+        assert_eq!(loc.to_string(), "line 5 (LOOP): @i");
+        let loc = map.get(5).unwrap();
+        assert_eq!(loc.to_string(), "line 9 (LOOP+3): D;JGT");
+        assert_eq!(map.get(7).unwrap().to_string(), "line 12 (END+1): 0;JMP");
+        assert_eq!(map.get(8), None);
+
+        assert_eq!(map.line_address(1), Some(0));
+        assert_eq!(map.line_address(7), Some(4));
+        assert_eq!(map.line_address(12), Some(7));
+        assert_eq!(map.line_address(13), None);
+
+        assert_eq!(map.label_address("LOOP"), Some(2));
+        assert_eq!(map.label_address("END"), Some(6));
+        assert_eq!(map.label_address("i"), None);
+        assert_eq!(
+            map.labels().collect::<Vec<_>>(),
+            vec![("END", 6), ("LOOP", 2)]
+        );
+    }
+
+    #[test]
+    fn predefined_symbol_cannot_be_redefined() {
+        let code = "
+        (R1)
+        @R1
+        0;JMP\n";
+        assert_eq!(
+            assemble(code).unwrap_err(),
+            vec![AsmError {
+                line: 2,
+                column: 10,
+                text: String::from("R1"),
+                kind: AsmErrorKind::DuplicateLabel,
+            }]
+        );
+    }
+
+    #[test]
+    fn constants_must_fit_in_15_bits() {
+        let code = "@32767\n@32768\n@70000\n@99999999999\n";
+        let e = |line, text: &str| AsmError {
+            line,
+            column: 2,
+            text: String::from(text),
+            kind: AsmErrorKind::ConstantOutOfRange,
+        };
+        assert_eq!(
+            assemble(code).unwrap_err(),
+            vec![e(2, "32768"), e(3, "70000"), e(4, "99999999999")]
+        );
+    }
+
+    #[test]
+    fn labels_must_fit_in_15_bits() {
+        let code = "D=0\n".repeat(0x8000) + "(END)\n@END\n";
+        assert_eq!(
+            assemble(&code).unwrap_err(),
+            vec![AsmError {
+                line: 0x8002,
+                column: 2,
+                text: String::from("END"),
+                kind: AsmErrorKind::ConstantOutOfRange,
+            }]
+        );
+    }
 }
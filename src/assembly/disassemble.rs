@@ -0,0 +1,154 @@
+use std::collections::BTreeSet;
+
+use crate::gates::bus16::Bus16;
+use crate::instruction::{Instruction, Jump};
+
+pub fn disassemble(bin: &[Bus16]) -> Result<String, String> {
+    let insts = bin
+        .iter()
+        .enumerate()
+        .map(|(i, word)| Instruction::decode(word).map_err(|e| format!("address {}: {}", i, e)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let targets = jump_targets(&insts);
+
+    let mut code = String::new();
+    for (pc, inst) in insts.iter().enumerate() {
+        if targets.contains(&(pc as i32)) {
+            code += &format!("({})\n", label(pc as i32));
+        }
+        match inst {
+            Instruction::A(a) if is_jump_address(&insts, pc) && targets.contains(&a.value()) => {
+                code += &format!("@{}\n", label(a.value()));
+            }
+            _ => code += &format!("{}\n", inst),
+        }
+    }
+    if targets.contains(&(insts.len() as i32)) {
+        code += &format!("({})\n", label(insts.len() as i32));
+    }
+    Ok(code)
+}
+
+fn label(addr: i32) -> String {
+    format!("L_{:04}", addr)
+}
+
+// an A-instruction that loads the target of the jump right after it
+fn is_jump_address(insts: &[Instruction], pc: usize) -> bool {
+    match insts.get(pc + 1) {
+        Some(Instruction::C(c)) => c.jump != Jump::None,
+        _ => false,
+    }
+}
+
+// labels can only be placed in front of an instruction or at the end of the program
+fn jump_targets(insts: &[Instruction]) -> BTreeSet<i32> {
+    insts
+        .iter()
+        .enumerate()
+        .filter_map(|(pc, inst)| match inst {
+            Instruction::A(a)
+                if is_jump_address(insts, pc) && a.value() as usize <= insts.len() =>
+            {
+                Some(a.value())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembly::assemble;
+    use crate::gates::bus16::into_i32;
+
+    fn words(bin: &[Bus16]) -> Vec<i32> {
+        bin.iter().map(into_i32).collect()
+    }
+
+    #[test]
+    fn disassemble_works() {
+        let code = "
+        @0
+        D=M
+        @1
+        D=D-M
+        @LESS
+        D;JLT
+        @0
+        MD=M+1
+        @2
+        M=D
+        (LESS)
+        @END
+        0;JMP
+        (END)
+        @END
+        0;JMP\n";
+        let bin = assemble(code).unwrap();
+
+        assert_eq!(
+            disassemble(&bin).unwrap(),
+            "@0\n\
+            D=M\n\
+            @1\n\
+            D=D-M\n\
+            @L_0010\n\
+            D;JLT\n\
+            @0\n\
+            MD=M+1\n\
+            @2\n\
+            M=D\n\
+            (L_0010)\n\
+            @L_0012\n\
+            0;JMP\n\
+            (L_0012)\n\
+            @L_0012\n\
+            0;JMP\n"
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let code = "
+        @100
+        D=A
+        @i
+        M=D
+        (LOOP)
+        @i
+        MD=M-1
+        @SCREEN
+        A=D+A
+        M=-1
+        @i
+        D=M
+        @LOOP
+        D;JGT
+        @9
+        0;JNE
+        @99
+        0;JMP
+        @END
+        0;JMP
+        (END)\n";
+        let bin = assemble(code).unwrap();
+        let asm = disassemble(&bin).unwrap();
+        assert_eq!(words(&assemble(&asm).unwrap()), words(&bin));
+        assert!(asm.ends_with("(L_0019)\n"));
+        assert!(asm.contains("@99\n"));
+    }
+
+    #[test]
+    fn undefined_instruction_is_reported() {
+        let bin = vec![
+            Instruction::a(1).unwrap().bus16(),
+            crate::gates::bus16::make_bus16(0b1110_0000_0100_0000),
+        ];
+        assert_eq!(
+            disassemble(&bin).unwrap_err(),
+            "address 1: undefined comp bits 0000001"
+        );
+    }
+}
@@ -0,0 +1,44 @@
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AsmErrorKind {
+    UnknownComp,
+    BadDest,
+    UnknownJump,
+    DuplicateLabel,
+    ConstantOutOfRange,
+    // a line that is none of the above, e.g. a malformed label or symbol
+    Syntax,
+}
+
+// line and column are 1-based
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AsmError {
+    pub line: usize,
+    pub column: usize,
+    pub text: String,
+    pub kind: AsmErrorKind,
+}
+
+impl fmt::Display for AsmErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AsmErrorKind::UnknownComp => "unknown comp",
+            AsmErrorKind::BadDest => "bad dest",
+            AsmErrorKind::UnknownJump => "unknown jump",
+            AsmErrorKind::DuplicateLabel => "duplicate label",
+            AsmErrorKind::ConstantOutOfRange => "constant out of range",
+            AsmErrorKind::Syntax => "syntax error",
+        })
+    }
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {} `{}`",
+            self.line, self.column, self.kind, self.text
+        )
+    }
+}
@@ -2,4 +2,15 @@ mod parser;
 
 pub mod asm;
 
+mod error;
+pub use self::error::{AsmError, AsmErrorKind};
+
+mod source_map;
+pub use self::source_map::{SourceLocation, SourceMap};
+
 mod assemble;
+pub(crate) use self::assemble::PREDEFINED_SYMBOLS;
+pub use self::assemble::{assemble, assemble_with_source_map};
+
+mod disassemble;
+pub use self::disassemble::disassemble;
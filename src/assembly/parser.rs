@@ -1,4 +1,5 @@
 use crate::instruction;
+use crate::instruction::Address;
 use crate::parser::text_parser::{numeric_parser, word_parser};
 use crate::parser::{
     AtomParser, DiscardParser, E2Parser, FuncParser, MustParser, OneOfParser, OptionParser, Parser,
@@ -6,31 +7,113 @@ use crate::parser::{
 };
 
 use super::asm::*;
+use super::error::{AsmError, AsmErrorKind};
 
-pub fn parse(code: &str) -> Result<Vec<E2<AsmInst, Label>>, String> {
-    let (x, p) = asm_parser()
-        .parse(&code.chars().collect(), 0)
-        .ok_or(String::from("failed to parse"))?;
-    if p == code.len() {
-        return Ok(x);
+// returns the lines that parsed along with an error for each line that did not
+pub fn parse(code: &str) -> (Vec<Line>, Vec<AsmError>) {
+    let mut lines = Vec::new();
+    let mut errors = Vec::new();
+    for (i, text) in code.split('\n').enumerate() {
+        let text = text.strip_suffix('\r').unwrap_or(text);
+        match parse_line(i + 1, text) {
+            Ok(Some(line)) => lines.push(line),
+            Ok(None) => (),
+            Err(e) => errors.push(e),
+        }
     }
-    Err(String::from("character remains"))
+    (lines, errors)
 }
 
-fn asm_parser(
-) -> RefinedParser<'static, char, Vec<Option<E2<AsmInst, Label>>>, Vec<E2<AsmInst, Label>>> {
-    RefinedParser::new(
-        RepeatParser::new(RefinedParser::new(
-            T2Parser::new(line_parser(), AtomParser::new('\n')),
-            |(l, _)| l,
-        )),
-        |mut v| v.drain(..).filter_map(|x| x).collect(),
-    )
+fn parse_line(number: usize, text: &str) -> Result<Option<Line>, AsmError> {
+    let chars = text.chars().collect::<Vec<_>>();
+    let column = chars.iter().take_while(|&&c| c == ' ').count() + 1;
+    match line_parser().parse(&chars, 0) {
+        Some((statement, p)) if p == chars.len() => {
+            if let Some(E2::A(AsmInst::A(A::Const(c)))) = statement {
+                if Address::of(c).is_none() {
+                    return Err(AsmError {
+                        line: number,
+                        column: column + 1,
+                        text: chars[column..]
+                            .iter()
+                            .take_while(|c| c.is_ascii_digit())
+                            .collect(),
+                        kind: AsmErrorKind::ConstantOutOfRange,
+                    });
+                }
+            }
+            Ok(statement.map(|statement| Line {
+                number,
+                column,
+                statement,
+            }))
+        }
+        _ => Err(diagnose(number, &chars)),
+    }
+}
+
+// finds which part of a line that line_parser rejected is to blame
+fn diagnose(number: usize, chars: &[char]) -> AsmError {
+    let start = chars.iter().take_while(|&&c| c == ' ').count();
+    let end = (start..chars.len())
+        .find(|&i| chars[i] == '/' && chars.get(i + 1) == Some(&'/'))
+        .unwrap_or(chars.len());
+    let end = start
+        + chars[start..end]
+            .iter()
+            .rposition(|&c| c != ' ')
+            .map_or(0, |p| p + 1);
+    let body = &chars[start..end];
+    let error = |offset: usize, part: &[char], kind: AsmErrorKind| AsmError {
+        line: number,
+        column: start + offset + 1,
+        text: part.iter().collect(),
+        kind,
+    };
+
+    match body.first() {
+        Some('@') => {
+            if body.len() > 1 && body[1..].iter().all(|c| c.is_ascii_digit()) {
+                return error(1, &body[1..], AsmErrorKind::ConstantOutOfRange);
+            }
+            return error(0, body, AsmErrorKind::Syntax);
+        }
+        Some('(') | None => return error(0, body, AsmErrorKind::Syntax),
+        _ => (),
+    }
+
+    let eq = body.iter().position(|&c| c == '=');
+    let comp_start = eq.map_or(0, |p| p + 1);
+    let semi = body[comp_start..]
+        .iter()
+        .position(|&c| c == ';')
+        .map(|p| comp_start + p);
+    let comp_end = semi.unwrap_or(body.len());
+    if let Some(eq) = eq {
+        if !parses_whole(&dest_parser(), &body[..eq + 1]) {
+            return error(0, &body[..eq], AsmErrorKind::BadDest);
+        }
+    }
+    let comp = &body[comp_start..comp_end];
+    if !parses_whole(&comp_parser(), comp) {
+        return error(comp_start, comp, AsmErrorKind::UnknownComp);
+    }
+    if let Some(semi) = semi {
+        if !parses_whole(&jump_parser(), &body[semi..]) {
+            return error(semi + 1, &body[semi + 1..], AsmErrorKind::UnknownJump);
+        }
+    }
+    error(0, body, AsmErrorKind::Syntax)
 }
 
-fn line_parser(
-) -> RefinedParser<'static, char, ((), Option<E2<AsmInst, Label>>, ()), Option<E2<AsmInst, Label>>>
-{
+fn parses_whole<T>(p: &dyn Parser<char, T>, part: &[char]) -> bool {
+    let tape = part.to_vec();
+    matches!(p.parse(&tape, 0), Some((_, n)) if n == tape.len())
+}
+
+type LineItem = Option<E2<AsmInst, Label>>;
+
+fn line_parser() -> RefinedParser<'static, char, ((), LineItem, ()), LineItem> {
     RefinedParser::new(
         T3Parser::new(
             DiscardParser::new(RepeatParser::new(AtomParser::new(' '))),
@@ -67,16 +150,14 @@ fn a_instruction_parser() -> RefinedParser<'static, char, (char, E2<i32, String>
     )
 }
 
-fn c_instruction_parser() -> RefinedParser<
-    'static,
-    char,
-    (
-        Option<instruction::Dest>,
-        (instruction::CompReg, instruction::Comp),
-        Option<instruction::Jump>,
-    ),
-    instruction::Computation,
-> {
+// dest, comp and jump as they are parsed, before the defaults fill in
+type CParts = (
+    Option<instruction::Dest>,
+    (instruction::CompReg, instruction::Comp),
+    Option<instruction::Jump>,
+);
+
+fn c_instruction_parser() -> RefinedParser<'static, char, CParts, instruction::Computation> {
     RefinedParser::new(
         T3Parser::new(
             OptionParser::new(dest_parser()),
@@ -137,15 +218,21 @@ fn dest_parser() -> MustParser<'static, char, instruction::Dest> {
             )),
             AtomParser::new('='),
         ),
-        |(v, _)| match v.iter().collect::<String>().as_str() {
-            "A" => Some(instruction::Dest::A),
-            "AD" => Some(instruction::Dest::AD),
-            "AM" => Some(instruction::Dest::AM),
-            "ADM" => Some(instruction::Dest::ADM),
-            "D" => Some(instruction::Dest::D),
-            "DM" => Some(instruction::Dest::DM),
-            "M" => Some(instruction::Dest::M),
-            _ => None,
+        |(mut v, _)| {
+            v.sort();
+            if v.windows(2).any(|w| w[0] == w[1]) {
+                return None;
+            }
+            match v.iter().collect::<String>().as_str() {
+                "A" => Some(instruction::Dest::A),
+                "AD" => Some(instruction::Dest::AD),
+                "AM" => Some(instruction::Dest::AM),
+                "ADM" => Some(instruction::Dest::ADM),
+                "D" => Some(instruction::Dest::D),
+                "DM" => Some(instruction::Dest::DM),
+                "M" => Some(instruction::Dest::M),
+                _ => None,
+            }
         },
     ))
 }
@@ -168,23 +255,27 @@ fn comp_parser() -> MustParser<'static, char, (instruction::CompReg, instruction
             "A" => Some((CR::A, C::A)),
             "!D" => Some((CR::A, C::NotD)),
             "!A" => Some((CR::A, C::NotA)),
+            "-D" => Some((CR::A, C::MinusD)),
+            "-A" => Some((CR::A, C::MinusA)),
             "D+1" => Some((CR::A, C::DPlus1)),
             "A+1" => Some((CR::A, C::APlus1)),
             "D-1" => Some((CR::A, C::DMinus1)),
             "A-1" => Some((CR::A, C::AMinus1)),
-            "D+A" => Some((CR::A, C::DPlusA)),
+            "D+A" | "A+D" => Some((CR::A, C::DPlusA)),
             "D-A" => Some((CR::A, C::DMinusA)),
             "A-D" => Some((CR::A, C::AMinusD)),
-            "D&A" => Some((CR::A, C::DAndA)),
-            "D|A" => Some((CR::A, C::DOrA)),
+            "D&A" | "A&D" => Some((CR::A, C::DAndA)),
+            "D|A" | "A|D" => Some((CR::A, C::DOrA)),
             "M" => Some((CR::M, C::A)),
             "!M" => Some((CR::M, C::NotA)),
             "-M" => Some((CR::M, C::MinusA)),
-            "D+M" => Some((CR::M, C::DPlusA)),
+            "M+1" => Some((CR::M, C::APlus1)),
+            "M-1" => Some((CR::M, C::AMinus1)),
+            "D+M" | "M+D" => Some((CR::M, C::DPlusA)),
             "D-M" => Some((CR::M, C::DMinusA)),
             "M-D" => Some((CR::M, C::AMinusD)),
-            "D&M" => Some((CR::M, C::DAndA)),
-            "D|M" => Some((CR::M, C::DOrA)),
+            "D&M" | "M&D" => Some((CR::M, C::DAndA)),
+            "D|M" | "M|D" => Some((CR::M, C::DOrA)),
             _ => None,
         },
     ))
@@ -227,8 +318,67 @@ fn comment_parser() -> DiscardParser<'static, char, (String, Vec<()>)> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::gates::bus16::into_i32;
     use crate::instruction::{Dest, Jump};
     use crate::parser::Parser;
+    use std::assert_matches;
+
+    #[test]
+    fn parse_keeps_positions() {
+        use instruction::*;
+
+        let (lines, errors) = parse("@1\n\n  (LOOP) // comment\r\n   D=M\n");
+        assert_eq!(errors, vec![]);
+        assert_eq!(
+            lines,
+            vec![
+                Line {
+                    number: 1,
+                    column: 1,
+                    statement: E2::A(AsmInst::A(A::Const(1))),
+                },
+                Line {
+                    number: 3,
+                    column: 3,
+                    statement: E2::B(Label(String::from("LOOP"))),
+                },
+                Line {
+                    number: 4,
+                    column: 4,
+                    statement: E2::A(AsmInst::C(Computation {
+                        dest: Dest::D,
+                        comp: (CompReg::M, Comp::A),
+                        jump: Jump::None,
+                    })),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_reports_every_error() {
+        let code = "@1\n  D=Q\nXY=D\nD;JXX // comment\n@40000\n(LOOP\n  M=D\nM=D+1;\n";
+        let (lines, errors) = parse(code);
+        assert_eq!(lines.len(), 2);
+        let e = |line, column, text: &str, kind| AsmError {
+            line,
+            column,
+            text: String::from(text),
+            kind,
+        };
+        assert_eq!(
+            errors,
+            vec![
+                e(2, 5, "Q", AsmErrorKind::UnknownComp),
+                e(3, 1, "XY", AsmErrorKind::BadDest),
+                e(4, 3, "JXX", AsmErrorKind::UnknownJump),
+                e(5, 2, "40000", AsmErrorKind::ConstantOutOfRange),
+                e(6, 1, "(LOOP", AsmErrorKind::Syntax),
+                e(8, 7, "", AsmErrorKind::UnknownJump),
+            ]
+        );
+        assert_eq!(errors[0].to_string(), "2:5: unknown comp `Q`");
+    }
 
     #[test]
     fn a_instruction_parser_works() {
@@ -305,6 +455,122 @@ mod tests {
         );
     }
 
+    #[test]
+    fn comp_parser_accepts_official_table() {
+        use instruction::{Comp as C, CompReg as CR};
+
+        let table = [
+            ("0", (CR::A, C::Zero), 0b0101010),
+            ("1", (CR::A, C::One), 0b0111111),
+            ("-1", (CR::A, C::MinusOne), 0b0111010),
+            ("D", (CR::A, C::D), 0b0001100),
+            ("A", (CR::A, C::A), 0b0110000),
+            ("!D", (CR::A, C::NotD), 0b0001101),
+            ("!A", (CR::A, C::NotA), 0b0110001),
+            ("-D", (CR::A, C::MinusD), 0b0001111),
+            ("-A", (CR::A, C::MinusA), 0b0110011),
+            ("D+1", (CR::A, C::DPlus1), 0b0011111),
+            ("A+1", (CR::A, C::APlus1), 0b0110111),
+            ("D-1", (CR::A, C::DMinus1), 0b0001110),
+            ("A-1", (CR::A, C::AMinus1), 0b0110010),
+            ("D+A", (CR::A, C::DPlusA), 0b0000010),
+            ("D-A", (CR::A, C::DMinusA), 0b0010011),
+            ("A-D", (CR::A, C::AMinusD), 0b0000111),
+            ("D&A", (CR::A, C::DAndA), 0b0000000),
+            ("D|A", (CR::A, C::DOrA), 0b0010101),
+            ("M", (CR::M, C::A), 0b1110000),
+            ("!M", (CR::M, C::NotA), 0b1110001),
+            ("-M", (CR::M, C::MinusA), 0b1110011),
+            ("M+1", (CR::M, C::APlus1), 0b1110111),
+            ("M-1", (CR::M, C::AMinus1), 0b1110010),
+            ("D+M", (CR::M, C::DPlusA), 0b1000010),
+            ("D-M", (CR::M, C::DMinusA), 0b1010011),
+            ("M-D", (CR::M, C::AMinusD), 0b1000111),
+            ("D&M", (CR::M, C::DAndA), 0b1000000),
+            ("D|M", (CR::M, C::DOrA), 0b1010101),
+        ];
+        assert_eq!(table.len(), 28);
+
+        let p = comp_parser();
+        for (text, expected, bits) in table {
+            let tape = text.chars().collect::<Vec<_>>();
+            let parsed = p.parse(&tape, 0);
+            assert_eq!(parsed, Some((expected, text.len())), "{}", text);
+            let inst = instruction::Instruction::C(instruction::Computation {
+                comp: parsed.unwrap().0,
+                dest: instruction::Dest::None,
+                jump: instruction::Jump::None,
+            });
+            let w = into_i32(&inst.bus16());
+            assert_eq!((w >> 6) & 0x7f, bits, "{}", text);
+        }
+    }
+
+    #[test]
+    fn comp_parser_accepts_commutative_spellings() {
+        use instruction::{Comp as C, CompReg as CR};
+
+        let table = [
+            ("A+D", (CR::A, C::DPlusA)),
+            ("A&D", (CR::A, C::DAndA)),
+            ("A|D", (CR::A, C::DOrA)),
+            ("M+D", (CR::M, C::DPlusA)),
+            ("M&D", (CR::M, C::DAndA)),
+            ("M|D", (CR::M, C::DOrA)),
+        ];
+
+        let p = comp_parser();
+        for (text, expected) in table {
+            let tape = text.chars().collect::<Vec<_>>();
+            assert_eq!(p.parse(&tape, 0), Some((expected, text.len())), "{}", text);
+        }
+        for text in ["A-M", "D+D", "1+1", "M+A", "-0"] {
+            let tape = text.chars().collect::<Vec<_>>();
+            assert_eq!(p.parse(&tape, 0), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn c_instruction_parser_accepts_every_dest() {
+        use instruction::*;
+
+        let table = [
+            (vec![""], Dest::None),
+            (vec!["M="], Dest::M),
+            (vec!["D="], Dest::D),
+            (vec!["MD=", "DM="], Dest::DM),
+            (vec!["A="], Dest::A),
+            (vec!["AM=", "MA="], Dest::AM),
+            (vec!["AD=", "DA="], Dest::AD),
+            (
+                vec!["AMD=", "ADM=", "MAD=", "MDA=", "DAM=", "DMA="],
+                Dest::ADM,
+            ),
+        ];
+        assert_eq!(table.len(), 8);
+
+        let p = c_instruction_parser();
+        for (spellings, dest) in table {
+            for prefix in spellings {
+                let text = String::from(prefix) + "D+1";
+                let tape = text.chars().collect::<Vec<_>>();
+                let (c, pos) = p.parse(&tape, 0).expect(&text);
+                assert_eq!(c.dest, dest, "{}", text);
+                assert_eq!(c.comp, (CompReg::A, Comp::DPlus1), "{}", text);
+                assert_eq!(pos, text.len(), "{}", text);
+            }
+        }
+        for text in ["AA=D", "MDM=D", "X=D"] {
+            let tape = text.chars().collect::<Vec<_>>();
+            assert_ne!(
+                p.parse(&tape, 0).map(|(_, p)| p),
+                Some(text.len()),
+                "{}",
+                text
+            );
+        }
+    }
+
     #[test]
     fn line_parser_works() {
         use instruction::*;
@@ -346,7 +612,7 @@ mod tests {
         let p = line_parser();
         assert_eq!(
             p.parse(&text, 0),
-            Some((Some(E2::A(AsmInst::A(A::Var(String::from("@SYMBOL"))))), 7))
+            Some((Some(E2::A(AsmInst::A(A::Var(String::from("SYMBOL"))))), 7))
         );
     }
 }
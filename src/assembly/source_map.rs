@@ -0,0 +1,89 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::parser::E2;
+
+use super::asm::{Label, Line};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SourceLocation {
+    pub line: usize,
+    // the statement without indentation or comment
+    pub text: String,
+    // the nearest label at or before this instruction, and how far past it we are
+    pub label: Option<(String, usize)>,
+}
+
+// ROM address -> assembly source
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SourceMap {
+    locations: Vec<SourceLocation>,
+    labels: BTreeMap<String, usize>,
+}
+
+impl SourceMap {
+    pub(super) fn of(code: &str, lines: &[Line]) -> Self {
+        let source: Vec<&str> = code.split('\n').collect();
+        let mut map = SourceMap::default();
+        let mut label: Option<(String, usize)> = None;
+        for line in lines {
+            match &line.statement {
+                E2::A(_) => {
+                    let raw = source[line.number - 1];
+                    let raw = raw.split("//").next().unwrap_or(raw);
+                    map.locations.push(SourceLocation {
+                        line: line.number,
+                        text: raw.trim().to_string(),
+                        label: label.clone(),
+                    });
+                    if let Some((_, offset)) = &mut label {
+                        *offset += 1;
+                    }
+                }
+                E2::B(Label(name)) => {
+                    map.labels.insert(name.clone(), map.locations.len());
+                    label = Some((name.clone(), 0));
+                }
+            }
+        }
+        map
+    }
+
+    pub fn get(&self, address: usize) -> Option<&SourceLocation> {
+        self.locations.get(address)
+    }
+
+    // the first instruction on or after a source line
+    pub fn line_address(&self, line: usize) -> Option<usize> {
+        self.locations.iter().position(|l| l.line >= line)
+    }
+
+    pub fn label_address(&self, label: &str) -> Option<usize> {
+        self.labels.get(label).copied()
+    }
+
+    // labels in name order
+    pub fn labels(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.labels.iter().map(|(l, &a)| (l.as_str(), a))
+    }
+
+    pub fn len(&self) -> usize {
+        self.locations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.locations.is_empty()
+    }
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}", self.line)?;
+        match &self.label {
+            Some((l, 0)) => write!(f, " ({})", l)?,
+            Some((l, offset)) => write!(f, " ({}+{})", l, offset)?,
+            None => (),
+        }
+        write!(f, ": {}", self.text)
+    }
+}
@@ -1,23 +1,203 @@
+use std::collections::BTreeSet;
+
 use crate::gates::bus16;
-use crate::gates::bus16::Bus16;
+use crate::gates::bus16::{into_i32, Bus16};
 use crate::general::Zero;
 use crate::infrastructure::sequential::*;
+use crate::instruction::{Comp, CompReg, Computation, Dest, Instruction, Jump};
+use crate::keyboard::Keyboard;
 use crate::primitive::Bit;
 
-use super::{Cpu, CpuInput, CpuOutput, DataMemoryInput, MutDataMemory, MutRom};
+use super::history::{Delta, History, LastWrite};
+use super::interface::{check_program_size, Computer};
+use super::snapshot::{trim_zeros, Snapshot};
+use super::trace::{TraceEntry, Tracer};
+use super::{
+    Cpu, CpuInput, CpuOutput, DataMemory, DataMemoryInput, GateDataMemory, GateRom, MutDataMemory,
+    MutRom, Rom,
+};
+
+// ROM and data memory addressed through mux/dmux over mutable RAM chips
+pub type MutComputer = MutMachine<MutRom, MutDataMemory>;
+
+// the same chips kept as immutable gate circuits; correct but far slower (run --gates)
+pub type GateComputer = MutMachine<GateRom, GateDataMemory>;
 
-// future work: should be constructed of gates
-pub struct MutComputer {
-    ram: MutDataMemory,
-    rom: MutRom,
+pub struct MutMachine<R: Rom, M: DataMemory> {
+    ram: M,
+    rom: R,
     cpu: Cpu,
+    keyboard: Keyboard,
+    breakpoints: BTreeSet<u16>,
+    cycles: u64,
+    tracer: Option<Tracer>,
+    history: Option<History<Cpu>>,
 }
 
-impl MutSC for MutComputer {
-    type Input = ();
-    type Output = ();
+impl<R: Rom, M: DataMemory> Computer for MutMachine<R, M> {
+    fn of(instructions: &[Bus16]) -> Result<Self, String> {
+        check_program_size(instructions)?;
+        Ok(MutMachine {
+            rom: R::of(instructions),
+            ram: M::new(),
+            cpu: Cpu::new(),
+            keyboard: Keyboard::new(),
+            breakpoints: BTreeSet::new(),
+            cycles: 0,
+            tracer: None,
+            history: None,
+        })
+    }
 
-    fn tick(&mut self, _: &Self::Input) -> Self::Output {
+    fn of_snapshot(s: &Snapshot) -> Self {
+        let rom: Vec<Bus16> = trim_zeros(&s.rom)
+            .iter()
+            .map(|&w| bus16::make_bus16(w as i32))
+            .collect();
+        // a snapshot's ROM has exactly the words ROM holds
+        let mut c = Self::of(&rom).unwrap();
+        c.poke_a(s.a as i16);
+        c.poke_d(s.d as i16);
+        c.poke_pc(s.pc);
+        for (addr, &w) in trim_zeros(&s.ram).iter().enumerate() {
+            if w != 0 {
+                c.poke_ram(addr as u16, w as i16);
+            }
+        }
+        c.keyboard = Keyboard::of_state(s.keyboard_cycle, s.key, s.key_events.clone());
+        c.ram.set_keyboard(bus16::make_bus16(s.key as i32));
+        c.cycles = s.cycles;
+        c
+    }
+
+    fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    fn trace(&mut self, tracer: Tracer) {
+        self.tracer = Some(tracer);
+    }
+
+    fn stop_trace(&mut self) -> Option<Tracer> {
+        self.tracer.take()
+    }
+
+    fn keep_history(&mut self, capacity: usize) {
+        self.history = if capacity == 0 {
+            None
+        } else {
+            Some(History::of(capacity))
+        };
+    }
+
+    fn history_len(&self) -> usize {
+        self.history.as_ref().map_or(0, History::len)
+    }
+
+    fn step_back(&mut self) -> bool {
+        let delta = match self.history.as_mut().and_then(History::pop) {
+            Some(d) => d,
+            None => return false,
+        };
+        self.cpu = delta.cpu;
+        self.keyboard.rewind(delta.key, delta.key_event);
+        self.ram.set_keyboard(bus16::make_bus16(delta.key as i32));
+        if let Some((addr, before, _)) = delta.write {
+            self.poke_ram(addr, before);
+        }
+        self.cycles = delta.cycle;
+        true
+    }
+
+    fn last_write(&self, addr: u16) -> Option<LastWrite> {
+        self.history.as_ref().and_then(|h| h.last_write(addr))
+    }
+
+    fn step(&mut self) -> TraceEntry {
+        let entry = self.cycle(true).unwrap();
+        if let Some(t) = &mut self.tracer {
+            t.record(&entry);
+        }
+        entry
+    }
+
+    fn add_breakpoint(&mut self, pc: u16) {
+        self.breakpoints.insert(pc);
+    }
+
+    fn remove_breakpoint(&mut self, pc: u16) {
+        self.breakpoints.remove(&pc);
+    }
+
+    fn breakpoints(&self) -> Box<dyn Iterator<Item = u16> + '_> {
+        Box::new(self.breakpoints.iter().copied())
+    }
+
+    fn peek_a(&self) -> i16 {
+        into_i32(&self.cpu.peek_address()) as i16
+    }
+
+    fn poke_a(&mut self, value: i16) {
+        self.cpu.poke_address(&bus16::make_bus16(value as i32));
+    }
+
+    fn peek_d(&self) -> i16 {
+        into_i32(&self.cpu.peek_data()) as i16
+    }
+
+    fn poke_d(&mut self, value: i16) {
+        self.cpu.poke_data(&bus16::make_bus16(value as i32));
+    }
+
+    fn peek_pc(&self) -> u16 {
+        into_i32(&self.cpu.peek_pc()) as u16
+    }
+
+    fn poke_pc(&mut self, value: u16) {
+        self.cpu.poke_pc(&bus16::make_bus16(value as i32));
+    }
+
+    fn peek_ram(&mut self, addr: u16) -> i16 {
+        into_i32(&self.ram.peek(addr as i32)) as i16
+    }
+
+    fn poke_ram(&mut self, addr: u16, value: i16) {
+        self.ram.poke(addr as i32, &bus16::make_bus16(value as i32));
+    }
+
+    fn peek_rom(&mut self, addr: u16) -> u16 {
+        into_i32(&self.rom.peek(addr as i32)) as u16
+    }
+
+    fn poke_rom(&mut self, addr: u16, value: u16) {
+        self.rom.poke(addr as i32, &bus16::make_bus16(value as i32));
+    }
+
+    fn keyboard(&mut self) -> &mut Keyboard {
+        &mut self.keyboard
+    }
+
+    fn reset(&mut self) {
+        let nop = Instruction::C(Computation {
+            comp: (CompReg::A, Comp::Zero),
+            dest: Dest::None,
+            jump: Jump::None,
+        });
+        let (_, cpu) = self.cpu.tick(&CpuInput {
+            in_m: Bus16::new(),
+            instruction: nop.bus16(),
+            reset: Bit::Positive,
+        });
+        self.cpu = cpu;
+    }
+}
+
+impl<R: Rom, M: DataMemory> MutMachine<R, M> {
+    // ticks once; describing the cycle costs a few register reads, so it is optional
+    fn cycle(&mut self, describe: bool) -> Option<TraceEntry> {
+        let (key_before, key_event) = (self.keyboard.key(), self.keyboard.pending());
+        let key = self.keyboard.tick(&());
+        self.ram.set_keyboard(bus16::make_bus16(key as i32));
         let (
             CpuOutput {
                 pc, address_m: a, ..
@@ -29,7 +209,13 @@ impl MutSC for MutComputer {
             reset: Bit::new(),
         });
         let instruction = self.rom.tick(&pc);
-        let address_m = bus16::into_bus13(&a);
+        let pc_before = self.peek_pc();
+        let before = if describe {
+            Some((pc_before, self.peek_a(), self.peek_d()))
+        } else {
+            None
+        };
+        let address_m = bus16::into_bus15(&a);
         let in_m = self.ram.tick(&DataMemoryInput {
             input: Bus16::new(),
             load: Bit::Negative,
@@ -40,28 +226,68 @@ impl MutSC for MutComputer {
             instruction,
             reset: Bit::Negative,
         });
-        self.cpu = new_cpu;
+        let cpu_before = std::mem::replace(&mut self.cpu, new_cpu);
+        if let Some(h) = &mut self.history {
+            h.push(Delta {
+                cycle: self.cycles,
+                pc: pc_before,
+                cpu: cpu_before,
+                key: key_before,
+                key_event,
+                write: match o.write_m {
+                    Bit::Positive => Some((
+                        into_i32(&a) as u16 & 0x7fff,
+                        into_i32(&in_m) as i16,
+                        into_i32(&o.out_m) as i16,
+                    )),
+                    Bit::Negative => None,
+                },
+            });
+        }
+        let entry = before.map(|(pc, a_before, d_before)| TraceEntry {
+            cycle: self.cycles,
+            pc,
+            instruction: into_i32(&instruction) as u16,
+            a: (a_before, self.peek_a()),
+            d: (d_before, self.peek_d()),
+            write: match o.write_m {
+                Bit::Positive => Some((into_i32(&a) as u16 & 0x7fff, into_i32(&o.out_m) as i16)),
+                Bit::Negative => None,
+            },
+        });
+        self.cycles += 1;
         self.ram.tick(&DataMemoryInput {
             input: o.out_m,
             load: o.write_m,
             address: address_m,
         });
+        entry
+    }
+}
+
+impl<R: Rom, M: DataMemory> MutSC for MutMachine<R, M> {
+    type Input = ();
+    type Output = ();
+
+    fn tick(&mut self, _: &Self::Input) -> Self::Output {
+        let entry = self.cycle(self.tracer.is_some());
+        if let (Some(e), Some(t)) = (&entry, &mut self.tracer) {
+            t.record(e);
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::gates::bus16::testing::into_i32;
+    use crate::computer::HaltReason;
     use crate::instruction::*;
 
-    use super::super::cpu::testing::CpuDebug;
-    use super::testing::ComputerDebug;
     use super::*;
 
     #[test]
     fn load_a() {
-        let is = vec![Instruction::A(1000).bus16()];
-        let mut c = MutComputer::of(&is);
+        let is = vec![Instruction::a(1000).unwrap().bus16()];
+        let mut c = MutComputer::of(&is).unwrap();
         for _ in 0..is.len() {
             c.tick(&());
         }
@@ -72,7 +298,7 @@ mod tests {
     #[test]
     fn load_d() {
         let is = vec![
-            Instruction::A(999).bus16(),
+            Instruction::a(999).unwrap().bus16(),
             Instruction::C(Computation {
                 comp: (CompReg::A, Comp::A),
                 dest: Dest::D,
@@ -80,7 +306,7 @@ mod tests {
             })
             .bus16(),
         ];
-        let mut c = MutComputer::of(&is);
+        let mut c = MutComputer::of(&is).unwrap();
         for _ in 0..is.len() {
             c.tick(&());
         }
@@ -91,14 +317,14 @@ mod tests {
     #[test]
     fn simple_store() {
         let is = vec![
-            Instruction::A(100).bus16(),
+            Instruction::a(100).unwrap().bus16(),
             Instruction::C(Computation {
                 comp: (CompReg::A, Comp::A),
                 dest: Dest::D,
                 jump: Jump::None,
             })
             .bus16(),
-            Instruction::A(0).bus16(),
+            Instruction::a(0).unwrap().bus16(),
             Instruction::C(Computation {
                 comp: (CompReg::A, Comp::D),
                 dest: Dest::M,
@@ -106,7 +332,7 @@ mod tests {
             })
             .bus16(),
         ];
-        let mut c = MutComputer::of(&is);
+        let mut c = MutComputer::of(&is).unwrap();
         for _ in 0..is.len() {
             c.tick(&());
         }
@@ -117,28 +343,28 @@ mod tests {
     #[test]
     fn a_to_memory_to_d() {
         let is = vec![
-            Instruction::A(57).bus16(),
+            Instruction::a(57).unwrap().bus16(),
             Instruction::C(Computation {
                 comp: (CompReg::A, Comp::A),
                 dest: Dest::D,
                 jump: Jump::None,
             })
             .bus16(),
-            Instruction::A(11).bus16(),
+            Instruction::a(11).unwrap().bus16(),
             Instruction::C(Computation {
                 comp: (CompReg::A, Comp::D),
                 dest: Dest::M,
                 jump: Jump::None,
             })
             .bus16(),
-            Instruction::A(0).bus16(),
+            Instruction::a(0).unwrap().bus16(),
             Instruction::C(Computation {
                 comp: (CompReg::A, Comp::A),
                 dest: Dest::D,
                 jump: Jump::None,
             })
             .bus16(),
-            Instruction::A(11).bus16(),
+            Instruction::a(11).unwrap().bus16(),
             Instruction::C(Computation {
                 comp: (CompReg::M, Comp::A),
                 dest: Dest::D,
@@ -146,7 +372,7 @@ mod tests {
             })
             .bus16(),
         ];
-        let mut c = MutComputer::of(&is);
+        let mut c = MutComputer::of(&is).unwrap();
         for _ in 0..is.len() {
             c.tick(&());
         }
@@ -156,21 +382,21 @@ mod tests {
     #[test]
     fn simple_add() {
         let is = vec![
-            Instruction::A(3).bus16(),
+            Instruction::a(3).unwrap().bus16(),
             Instruction::C(Computation {
                 comp: (CompReg::A, Comp::A),
                 dest: Dest::D,
                 jump: Jump::None,
             })
             .bus16(),
-            Instruction::A(5).bus16(),
+            Instruction::a(5).unwrap().bus16(),
             Instruction::C(Computation {
                 comp: (CompReg::A, Comp::DPlusA),
                 dest: Dest::D,
                 jump: Jump::None,
             })
             .bus16(),
-            Instruction::A(10).bus16(),
+            Instruction::a(10).unwrap().bus16(),
             Instruction::C(Computation {
                 comp: (CompReg::A, Comp::D),
                 dest: Dest::M,
@@ -178,7 +404,7 @@ mod tests {
             })
             .bus16(),
         ];
-        let mut c = MutComputer::of(&is);
+        let mut c = MutComputer::of(&is).unwrap();
         for _ in 0..is.len() {
             c.tick(&());
         }
@@ -190,28 +416,28 @@ mod tests {
     #[test]
     fn d_plus_m() {
         let is = vec![
-            Instruction::A(5).bus16(),
+            Instruction::a(5).unwrap().bus16(),
             Instruction::C(Computation {
                 comp: (CompReg::A, Comp::A),
                 dest: Dest::D,
                 jump: Jump::None,
             })
             .bus16(),
-            Instruction::A(0).bus16(),
+            Instruction::a(0).unwrap().bus16(),
             Instruction::C(Computation {
                 comp: (CompReg::A, Comp::D),
                 dest: Dest::M,
                 jump: Jump::None,
             })
             .bus16(),
-            Instruction::A(9).bus16(),
+            Instruction::a(9).unwrap().bus16(),
             Instruction::C(Computation {
                 comp: (CompReg::A, Comp::A),
                 dest: Dest::D,
                 jump: Jump::None,
             })
             .bus16(),
-            Instruction::A(0).bus16(),
+            Instruction::a(0).unwrap().bus16(),
             Instruction::C(Computation {
                 comp: (CompReg::M, Comp::DPlusA),
                 dest: Dest::M,
@@ -219,7 +445,7 @@ mod tests {
             })
             .bus16(),
         ];
-        let mut c = MutComputer::of(&is);
+        let mut c = MutComputer::of(&is).unwrap();
         for _ in 0..is.len() {
             c.tick(&());
         }
@@ -230,28 +456,28 @@ mod tests {
     #[test]
     fn d_minus_m() {
         let is = vec![
-            Instruction::A(5).bus16(),
+            Instruction::a(5).unwrap().bus16(),
             Instruction::C(Computation {
                 comp: (CompReg::A, Comp::A),
                 dest: Dest::D,
                 jump: Jump::None,
             })
             .bus16(),
-            Instruction::A(0).bus16(),
+            Instruction::a(0).unwrap().bus16(),
             Instruction::C(Computation {
                 comp: (CompReg::A, Comp::D),
                 dest: Dest::M,
                 jump: Jump::None,
             })
             .bus16(),
-            Instruction::A(9).bus16(),
+            Instruction::a(9).unwrap().bus16(),
             Instruction::C(Computation {
                 comp: (CompReg::A, Comp::A),
                 dest: Dest::D,
                 jump: Jump::None,
             })
             .bus16(),
-            Instruction::A(0).bus16(),
+            Instruction::a(0).unwrap().bus16(),
             Instruction::C(Computation {
                 comp: (CompReg::M, Comp::DMinusA),
                 dest: Dest::D,
@@ -265,7 +491,7 @@ mod tests {
             })
             .bus16(),
         ];
-        let mut c = MutComputer::of(&is);
+        let mut c = MutComputer::of(&is).unwrap();
         for _ in 0..is.len() {
             c.tick(&());
         }
@@ -296,7 +522,7 @@ mod tests {
             })
             .bus16(),
         ];
-        let mut c = MutComputer::of(&is);
+        let mut c = MutComputer::of(&is).unwrap();
         for _ in 0..is.len() {
             c.tick(&());
         }
@@ -307,7 +533,7 @@ mod tests {
     #[test]
     fn jump_eq() {
         let is = vec![
-            Instruction::A(100).bus16(),
+            Instruction::a(100).unwrap().bus16(),
             Instruction::C(Computation {
                 comp: (CompReg::A, Comp::Zero),
                 dest: Dest::None,
@@ -315,7 +541,7 @@ mod tests {
             })
             .bus16(),
         ];
-        let mut c = MutComputer::of(&is);
+        let mut c = MutComputer::of(&is).unwrap();
         for _ in 0..is.len() {
             c.tick(&());
         }
@@ -326,7 +552,7 @@ mod tests {
     #[test]
     fn no_jump_eq() {
         let is = vec![
-            Instruction::A(100).bus16(),
+            Instruction::a(100).unwrap().bus16(),
             Instruction::C(Computation {
                 comp: (CompReg::A, Comp::A),
                 dest: Dest::None,
@@ -334,7 +560,7 @@ mod tests {
             })
             .bus16(),
         ];
-        let mut c = MutComputer::of(&is);
+        let mut c = MutComputer::of(&is).unwrap();
         for _ in 0..is.len() {
             c.tick(&());
         }
@@ -345,7 +571,7 @@ mod tests {
     #[test]
     fn no_jump_neq() {
         let is = vec![
-            Instruction::A(100).bus16(),
+            Instruction::a(100).unwrap().bus16(),
             Instruction::C(Computation {
                 comp: (CompReg::A, Comp::Zero),
                 dest: Dest::None,
@@ -353,7 +579,7 @@ mod tests {
             })
             .bus16(),
         ];
-        let mut c = MutComputer::of(&is);
+        let mut c = MutComputer::of(&is).unwrap();
         for _ in 0..is.len() {
             c.tick(&());
         }
@@ -364,7 +590,7 @@ mod tests {
     #[test]
     fn increment_memory() {
         let is = vec![
-            Instruction::A(1).bus16(),
+            Instruction::a(1).unwrap().bus16(),
             Instruction::C(Computation {
                 comp: (CompReg::M, Comp::APlus1),
                 dest: Dest::M,
@@ -390,7 +616,7 @@ mod tests {
             })
             .bus16(),
         ];
-        let mut c = MutComputer::of(&is);
+        let mut c = MutComputer::of(&is).unwrap();
         for _ in 0..is.len() {
             c.tick(&());
         }
@@ -403,14 +629,14 @@ mod tests {
     fn increment_loop() {
         let end = 100;
         let is = vec![
-            Instruction::A(10).bus16(),
+            Instruction::a(10).unwrap().bus16(),
             Instruction::C(Computation {
                 comp: (CompReg::A, Comp::A),
                 dest: Dest::D,
                 jump: Jump::None,
             })
             .bus16(),
-            Instruction::A(0).bus16(),
+            Instruction::a(0).unwrap().bus16(),
             Instruction::C(Computation {
                 comp: (CompReg::M, Comp::APlus1),
                 dest: Dest::M,
@@ -423,14 +649,14 @@ mod tests {
                 jump: Jump::None,
             })
             .bus16(),
-            Instruction::A(0).bus16(),
+            Instruction::a(0).unwrap().bus16(),
             Instruction::C(Computation {
                 comp: (CompReg::A, Comp::D),
                 dest: Dest::None,
                 jump: Jump::NEq,
             })
             .bus16(),
-            Instruction::A(end).bus16(),
+            Instruction::a(end).unwrap().bus16(),
             Instruction::C(Computation {
                 comp: (CompReg::A, Comp::Zero),
                 dest: Dest::None,
@@ -438,7 +664,7 @@ mod tests {
             })
             .bus16(),
         ];
-        let mut c = MutComputer::of(&is);
+        let mut c = MutComputer::of(&is).unwrap();
         for _ in 0..100 {
             c.tick(&());
             let pc = into_i32(&c.cpu.peek_pc());
@@ -458,14 +684,14 @@ mod tests {
         let end = 100;
         let is = vec![
             // i=1
-            Instruction::A(1).bus16(), // 0
+            Instruction::a(1).unwrap().bus16(), // 0
             Instruction::C(Computation {
                 comp: (CompReg::A, Comp::A),
                 dest: Dest::D,
                 jump: Jump::None,
             })
             .bus16(), // 1
-            Instruction::A(i).bus16(), // 2
+            Instruction::a(i).unwrap().bus16(), // 2
             Instruction::C(Computation {
                 comp: (CompReg::A, Comp::D),
                 dest: Dest::M,
@@ -474,14 +700,14 @@ mod tests {
             .bus16(), // 3
             //
             // sum=0
-            Instruction::A(0).bus16(), // 4
+            Instruction::a(0).unwrap().bus16(), // 4
             Instruction::C(Computation {
                 comp: (CompReg::A, Comp::A),
                 dest: Dest::D,
                 jump: Jump::None,
             })
             .bus16(), // 5
-            Instruction::A(sum).bus16(), // 6
+            Instruction::a(sum).unwrap().bus16(), // 6
             Instruction::C(Computation {
                 comp: (CompReg::A, Comp::D),
                 dest: Dest::M,
@@ -490,7 +716,7 @@ mod tests {
             .bus16(), // 7
             //
             // D = i
-            Instruction::A(i).bus16(), // 8
+            Instruction::a(i).unwrap().bus16(), // 8
             Instruction::C(Computation {
                 comp: (CompReg::M, Comp::A),
                 dest: Dest::D,
@@ -499,14 +725,14 @@ mod tests {
             .bus16(), // 9
             //
             // GOTO end if i > 10
-            Instruction::A(10).bus16(), // 10
+            Instruction::a(10).unwrap().bus16(), // 10
             Instruction::C(Computation {
                 comp: (CompReg::A, Comp::DMinusA),
                 dest: Dest::D,
                 jump: Jump::None,
             })
             .bus16(), // 11
-            Instruction::A(end).bus16(), // 12
+            Instruction::a(end).unwrap().bus16(), // 12
             Instruction::C(Computation {
                 comp: (CompReg::A, Comp::D),
                 dest: Dest::None,
@@ -515,7 +741,7 @@ mod tests {
             .bus16(), // 13
             //
             // D = i
-            Instruction::A(i).bus16(), // 14
+            Instruction::a(i).unwrap().bus16(), // 14
             Instruction::C(Computation {
                 comp: (CompReg::M, Comp::A),
                 dest: Dest::D,
@@ -524,7 +750,7 @@ mod tests {
             .bus16(), // 15
             //
             // sum = sum + i
-            Instruction::A(sum).bus16(), // 16
+            Instruction::a(sum).unwrap().bus16(), // 16
             Instruction::C(Computation {
                 comp: (CompReg::M, Comp::DPlusA),
                 dest: Dest::M,
@@ -533,7 +759,7 @@ mod tests {
             .bus16(), // 17
             //
             // i++
-            Instruction::A(i).bus16(), // 18
+            Instruction::a(i).unwrap().bus16(), // 18
             Instruction::C(Computation {
                 comp: (CompReg::M, Comp::APlus1),
                 dest: Dest::M,
@@ -542,7 +768,7 @@ mod tests {
             .bus16(), // 19
             //
             // goto 8
-            Instruction::A(8).bus16(), // 20
+            Instruction::a(8).unwrap().bus16(), // 20
             Instruction::C(Computation {
                 comp: (CompReg::A, Comp::Zero),
                 dest: Dest::None,
@@ -550,7 +776,7 @@ mod tests {
             })
             .bus16(), //21
         ];
-        let mut c = MutComputer::of(&is);
+        let mut c = MutComputer::of(&is).unwrap();
         for _ in 0..300 {
             c.tick(&());
             let pc = into_i32(&c.cpu.peek_pc());
@@ -561,30 +787,403 @@ mod tests {
         assert_eq!(into_i32(&c.cpu.peek_pc()), end);
         assert_eq!(into_i32(&c.ram.peek(sum)), 55);
     }
-}
 
-pub mod testing {
-    use crate::gates::bus16::testing::into_i32;
+    #[test]
+    fn screen_and_keyboard_are_mapped() {
+        let code = "
+        @KBD
+        D=M
+        @SCREEN
+        M=D
+        @24575
+        M=-1
+        @KBD
+        M=1\n";
+        let bin = crate::assembly::assemble(code).unwrap();
+        let mut c = MutComputer::of(&bin).unwrap();
+        c.keyboard().press(75);
+        for _ in 0..bin.len() {
+            c.tick(&());
+        }
+        assert_eq!(c.peek_ram(0x4000), 75);
+        assert_eq!(c.peek_ram(0x5fff), -1);
+        assert_eq!(c.peek_ram(0x6000), 75);
+        assert_eq!(c.peek_ram(0x0000), 0);
+        assert_eq!(c.peek_ram(0x1fff), 0);
+    }
 
-    use super::super::memory::testing;
-    use super::*;
+    fn program_sees_the_memory_map<C: Computer>() {
+        let code = "
+        @KBD
+        D=M
+        @16
+        M=D
+        @SCREEN
+        M=D
+        @24575
+        M=-1
+        @KBD
+        M=1\n";
+        let bin = crate::assembly::assemble(code).unwrap();
+        let mut c = C::of(&bin).unwrap();
+        c.keyboard().press(75);
+        for _ in 0..bin.len() {
+            c.tick(&());
+        }
+        assert_eq!(c.peek_ram(16), 75);
+        assert_eq!(c.peek_ram(0x4000), 75);
+        assert_eq!(c.peek_ram(0x5fff), -1);
+        assert_eq!(c.peek_ram(0x6000), 75);
+        assert_eq!(c.peek_ram(0x0000), 0);
+    }
 
-    pub trait ComputerDebug {
-        fn of(instructions: &Vec<Bus16>) -> Self;
-        fn peek_ram(&mut self, addr: i32) -> i32;
+    #[test]
+    fn gate_computer_works() {
+        program_sees_the_memory_map::<MutComputer>();
+        program_sees_the_memory_map::<GateComputer>();
     }
 
-    impl ComputerDebug for MutComputer {
-        fn of(instructions: &Vec<Bus16>) -> Self {
-            MutComputer {
-                rom: MutRom::of(instructions),
-                ram: MutDataMemory::new(),
-                cpu: Cpu::new(),
-            }
+    #[test]
+    fn programs_must_fit_in_rom() {
+        let bin = vec![Instruction::a(0).unwrap().bus16(); 0x8001];
+        assert_eq!(
+            MutComputer::of(&bin).err(),
+            Some(String::from(
+                "program has 32769 words, more than the 32768 ROM holds"
+            ))
+        );
+        assert!(crate::computer::FastComputer::of(&bin).is_err());
+        assert!(crate::computer::FastComputer::of(&bin[..0x8000]).is_ok());
+    }
+
+    #[test]
+    fn screen_works() {
+        let code = "
+        @SCREEN
+        M=1
+        @24575
+        M=-1\n";
+        let bin = crate::assembly::assemble(code).unwrap();
+        let mut c = MutComputer::of(&bin).unwrap();
+        for _ in 0..bin.len() {
+            c.tick(&());
         }
+        let s = c.screen();
+        assert!(s.pixel(0, 0));
+        assert!(!s.pixel(1, 0));
+        assert!((496..512).all(|x| s.pixel(x, 255)));
+        assert_eq!(s.words().iter().filter(|&&w| w != 0).count(), 2);
+    }
 
-        fn peek_ram(&mut self, addr: i32) -> i32 {
-            into_i32(&self.ram.peek(addr))
+    #[test]
+    fn scheduled_keys_reach_the_program() {
+        // counts the cycles spent in each loop iteration while the key is up
+        let code = "
+        (WAIT)
+        @KBD
+        D=M
+        @WAIT
+        D;JEQ
+        @KBD
+        D=M
+        @0
+        M=D
+        (END)
+        @END
+        0;JMP\n";
+        let bin = crate::assembly::assemble(code).unwrap();
+        let mut c = MutComputer::of(&bin).unwrap();
+        c.keyboard().schedule(10, crate::keyboard::UP);
+        for _ in 0..9 {
+            c.tick(&());
+        }
+        assert_eq!(c.peek_ram(0), 0);
+        for _ in 0..20 {
+            c.tick(&());
+        }
+        assert_eq!(c.peek_ram(0), 131);
+    }
+
+    #[test]
+    fn reset_keeps_ram() {
+        let code = "
+        @0
+        M=M+1
+        @1
+        D=A
+        (END)
+        @END
+        0;JMP\n";
+        let bin = crate::assembly::assemble(code).unwrap();
+        let mut c = MutComputer::of(&bin).unwrap();
+        for _ in 0..10 {
+            c.tick(&());
+        }
+        assert_eq!(c.peek_ram(0), 1);
+        assert_ne!(c.peek_pc(), 0);
+        c.reset();
+        assert_eq!(c.peek_pc(), 0);
+        assert_eq!(into_i32(&c.cpu.peek_data()), 1);
+        assert_eq!(c.peek_ram(0), 1);
+        for _ in 0..10 {
+            c.tick(&());
         }
+        assert_eq!(c.peek_ram(0), 2);
+    }
+
+    #[test]
+    fn peek_and_poke_work() {
+        let mut c = MutComputer::of(&[]).unwrap();
+        // D=D+A, M=D
+        c.poke_rom(7, 0b1110_0000_1001_0000);
+        c.poke_rom(8, 0b1110_0011_0000_1000);
+        assert_eq!(c.peek_rom(7), 0b1110_0000_1001_0000);
+        c.poke_pc(7);
+        c.poke_a(300);
+        c.poke_d(-5);
+        c.poke_ram(300, 1);
+        assert_eq!((c.peek_pc(), c.peek_a(), c.peek_d()), (7, 300, -5));
+        assert_eq!(c.peek_ram(300), 1);
+        c.tick(&());
+        c.tick(&());
+        assert_eq!((c.peek_pc(), c.peek_a(), c.peek_d()), (9, 300, 295));
+        assert_eq!(c.peek_ram(300), 295);
+    }
+
+    #[test]
+    fn run_until_halt_works() {
+        let code = "
+        @3
+        D=A
+        (LOOP)
+        @0
+        M=M+1
+        D=D-1
+        @LOOP
+        D;JGT
+        (END)
+        @END
+        0;JMP\n";
+        let bin = crate::assembly::assemble(code).unwrap();
+        let mut c = MutComputer::of(&bin).unwrap();
+        assert_eq!(c.run_until_halt(1000), (2 + 3 * 5, HaltReason::Halted));
+        assert_eq!(c.peek_ram(0), 3);
+        assert_eq!(c.peek_pc(), 7);
+        assert_eq!(c.run_until_halt(1000), (0, HaltReason::Halted));
+
+        let mut c = MutComputer::of(&bin).unwrap();
+        assert_eq!(c.run_until_halt(10), (10, HaltReason::CycleLimit));
+        assert_eq!(c.run_until_halt(1000), (7, HaltReason::Halted));
+    }
+
+    #[test]
+    fn run_until_halt_stops_at_breakpoints() {
+        let code = "
+        @3
+        D=A
+        (LOOP)
+        @0
+        M=M+1
+        D=D-1
+        @LOOP
+        D;JGT
+        (END)
+        @END
+        0;JMP\n";
+        let bin = crate::assembly::assemble(code).unwrap();
+        let mut c = MutComputer::of(&bin).unwrap();
+        c.add_breakpoint(4);
+        assert_eq!(c.run_until_halt(1000), (4, HaltReason::Breakpoint));
+        assert_eq!(c.peek_ram(0), 1);
+        assert_eq!(c.run_until_halt(1000), (5, HaltReason::Breakpoint));
+        assert_eq!(c.peek_ram(0), 2);
+        c.remove_breakpoint(4);
+        assert_eq!(c.run_until_halt(1000), (8, HaltReason::Halted));
+        assert_eq!(c.peek_ram(0), 3);
+    }
+
+    #[test]
+    fn only_the_halt_idiom_is_halted() {
+        // the loop at 2 increments RAM[0] forever
+        let code = "
+        @0
+        M=0
+        (LOOP)
+        @0
+        M=M+1
+        @LOOP
+        0;JMP\n";
+        let bin = crate::assembly::assemble(code).unwrap();
+        let mut c = MutComputer::of(&bin).unwrap();
+        assert_eq!(c.run_until_halt(100), (100, HaltReason::CycleLimit));
+    }
+
+    #[test]
+    fn trace_works() {
+        use crate::computer::trace::testing::Buffer;
+        use crate::computer::TraceFormat;
+
+        let code = "
+        @7
+        D=A
+        @100
+        M=D+1
+        (END)
+        @END
+        0;JMP\n";
+        let bin = crate::assembly::assemble(code).unwrap();
+        let mut c = MutComputer::of(&bin).unwrap();
+        let buf = Buffer::default();
+        c.trace(Tracer::of(Box::new(buf.clone()), TraceFormat::Text));
+        assert_eq!(c.run_until_halt(100), (4, HaltReason::Halted));
+        c.stop_trace().unwrap().finish().unwrap();
+        c.tick(&());
+        assert_eq!(c.cycles(), 5);
+        let text = buf.text();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "     0     0: @7           A=0->7 D=0->0",
+                "     1     1: D=A          A=7->7 D=0->7",
+                "     2     2: @100         A=7->100 D=7->7",
+                "     3     3: M=D+1        A=100->100 D=7->7 RAM[100]=8",
+            ]
+        );
+    }
+
+    #[test]
+    fn snapshot_works() {
+        // counts RAM[16] up while reading the keyboard into RAM[17]
+        let code = "(LOOP)\n@16\nM=M+1\n@KBD\nD=M\n@17\nM=D\n@LOOP\n0;JMP\n";
+        let bin = crate::assembly::assemble(code).unwrap();
+        let mut c = MutComputer::of(&bin).unwrap();
+        c.keyboard().schedule(30, 65);
+        c.poke_ram(0x4000, -1);
+        c.run_until_halt(20);
+        let s = c.snapshot();
+        assert_eq!(s.cycles, 20);
+        assert_eq!(s.rom.len(), 0x8000);
+        assert_eq!(s.ram[16], 3);
+
+        let mut restored = MutComputer::of_snapshot(&s);
+        assert_eq!(restored.snapshot(), s);
+        c.run_until_halt(40);
+        restored.run_until_halt(40);
+        assert_eq!(restored.snapshot(), c.snapshot());
+        assert_eq!(restored.peek_ram(17), 65);
+        assert_eq!(restored.peek_ram(0x4000), -1);
+        assert_eq!(restored.cycles(), 60);
+    }
+
+    // the address bus has 15 bits, so A = 0x8010 writes RAM[16]
+    fn high_a_is_recorded_as_15_bits<C: Computer>() {
+        let code = "@16\nD=A\n@32767\nD=D+A\nD=D+1\nA=D\nM=1\nD=M\n";
+        let mut c = C::of(&crate::assembly::assemble(code).unwrap()).unwrap();
+        c.keep_history(10);
+        let entries: Vec<TraceEntry> = (0..8).map(|_| c.step()).collect();
+        assert_eq!(entries[6].a.0 as u16, 0x8010);
+        assert_eq!(entries[6].write, Some((16, 1)));
+        assert_eq!(entries[7].read(), Some(16));
+        assert_eq!(c.last_write(16).map(|w| (w.pc, w.after)), Some((6, 1)));
+        assert_eq!(c.peek_ram(16), 1);
+        c.step_back();
+        c.step_back();
+        assert_eq!(c.peek_ram(16), 0);
+    }
+
+    #[test]
+    fn writes_use_15_bit_addresses() {
+        high_a_is_recorded_as_15_bits::<MutComputer>();
+        high_a_is_recorded_as_15_bits::<crate::computer::FastComputer>();
+    }
+
+    #[test]
+    fn step_back_works() {
+        let code = "(LOOP)\n@16\nM=M+1\n@KBD\nD=M\n@17\nM=D\n@LOOP\n0;JMP\n";
+        let bin = crate::assembly::assemble(code).unwrap();
+        let mut c = MutComputer::of(&bin).unwrap();
+        c.keep_history(100);
+        c.keyboard().schedule(10, 65);
+        let states: Vec<(u16, i16, i16, i16, i16)> = (0..30)
+            .map(|_| {
+                let s = (
+                    c.peek_pc(),
+                    c.peek_a(),
+                    c.peek_d(),
+                    c.peek_ram(16),
+                    c.peek_ram(17),
+                );
+                c.tick(&());
+                s
+            })
+            .collect();
+        assert_eq!(c.history_len(), 30);
+        for s in states.iter().rev() {
+            assert!(c.step_back());
+            assert_eq!(
+                (
+                    c.peek_pc(),
+                    c.peek_a(),
+                    c.peek_d(),
+                    c.peek_ram(16),
+                    c.peek_ram(17)
+                ),
+                *s
+            );
+        }
+        assert!(!c.step_back());
+        assert_eq!(c.cycles(), 0);
+        // replaying sees the key press again
+        c.run_until_halt(30);
+        assert_eq!((c.peek_ram(16), c.peek_ram(17)), (4, 65));
+    }
+
+    #[test]
+    fn history_is_bounded() {
+        let bin = crate::assembly::assemble("(LOOP)\n@16\nM=M+1\n@LOOP\n0;JMP\n").unwrap();
+        let mut c = MutComputer::of(&bin).unwrap();
+        c.keep_history(5);
+        c.run_until_halt(20);
+        assert_eq!(c.history_len(), 5);
+        assert_eq!(c.reverse_continue(100), (5, HaltReason::HistoryExhausted));
+        assert_eq!(c.cycles(), 15);
+        c.keep_history(0);
+        c.tick(&());
+        assert!(!c.step_back());
+    }
+
+    #[test]
+    fn reverse_continue_works() {
+        let bin = crate::assembly::assemble("(LOOP)\n@16\nM=M+1\n@LOOP\n0;JMP\n").unwrap();
+        let mut c = MutComputer::of(&bin).unwrap();
+        c.keep_history(1000);
+        c.run_until_halt(22);
+        c.add_breakpoint(2);
+        assert_eq!(c.reverse_continue(100), (4, HaltReason::Breakpoint));
+        assert_eq!((c.cycles(), c.peek_pc()), (18, 2));
+        assert_eq!(c.reverse_continue(100), (4, HaltReason::Breakpoint));
+        assert_eq!(c.cycles(), 14);
+        assert_eq!(c.reverse_continue(3), (3, HaltReason::CycleLimit));
+    }
+
+    #[test]
+    fn last_write_works() {
+        let code = "@16\nM=1\n@17\nM=-1\n@16\nM=M+1\n(END)\n@END\n0;JMP\n";
+        let bin = crate::assembly::assemble(code).unwrap();
+        let mut c = MutComputer::of(&bin).unwrap();
+        c.keep_history(100);
+        c.run_until_halt(100);
+        assert_eq!(
+            c.last_write(16),
+            Some(LastWrite {
+                cycle: 5,
+                pc: 5,
+                before: 1,
+                after: 2
+            })
+        );
+        assert_eq!(c.last_write(17).map(|w| (w.pc, w.after)), Some((3, -1)));
+        assert_eq!(c.last_write(18), None);
     }
 }
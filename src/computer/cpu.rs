@@ -127,96 +127,48 @@ impl FeedforwardSCDef<CpuRegisters> for CpuDef {
     }
 }
 
-pub mod testing {
-    use crate::instruction::*;
+// registers are read and written in place, without a clock tick
+impl Cpu {
+    pub fn peek_data(&self) -> Bus16 {
+        self.peek().peek().at(0).value()
+    }
 
-    use super::*;
+    pub fn peek_address(&self) -> Bus16 {
+        self.peek().peek().at(1).value()
+    }
 
-    pub trait CpuDebug {
-        fn peek_data(&self) -> Bus16;
-        fn peek_address(&self) -> Bus16;
-        fn peek_pc(&self) -> Bus16;
+    pub fn peek_pc(&self) -> Bus16 {
+        self.peek().peek().at(2).value()
     }
 
-    impl CpuDebug for Cpu {
-        fn peek_data(&self) -> Bus16 {
-            let (o, _) = self.tick(&CpuInput {
-                in_m: Bus16::new(),
-                instruction: Instruction::C(Computation {
-                    comp: (CompReg::A, Comp::D),
-                    dest: Dest::A,
-                    jump: Jump::None,
-                })
-                .bus16(),
-                reset: Bit::Negative,
-            });
-            o.out_m
-        }
+    pub fn poke_data(&mut self, value: &Bus16) {
+        self.peek_mut().peek_mut().at_mut(0).set(value);
+    }
 
-        fn peek_address(&self) -> Bus16 {
-            let (o, _) = self.tick(&CpuInput {
-                in_m: Bus16::new(),
-                instruction: Instruction::C(Computation {
-                    comp: (CompReg::A, Comp::A),
-                    dest: Dest::A,
-                    jump: Jump::None,
-                })
-                .bus16(),
-                reset: Bit::Negative,
-            });
-            o.address_m
-        }
+    pub fn poke_address(&mut self, value: &Bus16) {
+        self.peek_mut().peek_mut().at_mut(1).set(value);
+    }
 
-        fn peek_pc(&self) -> Bus16 {
-            let (o, _) = self.tick(&CpuInput {
-                in_m: Bus16::new(),
-                instruction: Instruction::C(Computation {
-                    comp: (CompReg::A, Comp::A),
-                    dest: Dest::A,
-                    jump: Jump::None,
-                })
-                .bus16(),
-                reset: Bit::Negative,
-            });
-            let p = o.pc;
-            [
-                Bit::Negative,
-                p[0x0],
-                p[0x1],
-                p[0x2],
-                p[0x3],
-                p[0x4],
-                p[0x5],
-                p[0x6],
-                p[0x7],
-                p[0x8],
-                p[0x9],
-                p[0xa],
-                p[0xb],
-                p[0xc],
-                p[0xd],
-                p[0xe],
-            ]
-        }
+    pub fn poke_pc(&mut self, value: &Bus16) {
+        self.peek_mut().peek_mut().at_mut(2).set(value);
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use std::assert_matches;
 
-    use crate::gates::bus16::testing::{into_i32, make_bus16};
+    use crate::gates::bus16::{into_i32, make_bus16};
     use crate::instruction::*;
 
-    use super::testing::CpuDebug;
-
     #[test]
     fn load_a_works() {
         let c = Cpu::new();
         for i in 0..10 {
             let (o, c) = c.tick(&CpuInput {
                 in_m: Bus16::new(),
-                instruction: Instruction::A(i).bus16(),
+                instruction: Instruction::a(i).unwrap().bus16(),
                 reset: Bit::Negative,
             });
             assert_eq!(into_i32(&c.peek_address()), i);
@@ -229,7 +181,7 @@ mod test {
         let c = Cpu::new();
         let (_, c) = c.tick(&CpuInput {
             in_m: Bus16::new(),
-            instruction: Instruction::A(123).bus16(),
+            instruction: Instruction::a(123).unwrap().bus16(),
             reset: Bit::Negative,
         });
         let (o, _) = c.tick(&CpuInput {
@@ -251,7 +203,7 @@ mod test {
         let c = Cpu::new();
         let (_, c) = c.tick(&CpuInput {
             in_m: Bus16::new(),
-            instruction: Instruction::A(11).bus16(),
+            instruction: Instruction::a(11).unwrap().bus16(),
             reset: Bit::Negative,
         });
         let (o, c) = c.tick(&CpuInput {
@@ -284,7 +236,7 @@ mod test {
         let c = Cpu::new();
         let (_, c) = c.tick(&CpuInput {
             in_m: Bus16::new(),
-            instruction: Instruction::A(15).bus16(),
+            instruction: Instruction::a(15).unwrap().bus16(),
             reset: Bit::Negative,
         });
         let (_, c) = c.tick(&CpuInput {
@@ -299,7 +251,7 @@ mod test {
         });
         let (_, c) = c.tick(&CpuInput {
             in_m: Bus16::new(),
-            instruction: Instruction::A(10).bus16(),
+            instruction: Instruction::a(10).unwrap().bus16(),
             reset: Bit::Negative,
         });
         let (o, c) = c.tick(&CpuInput {
@@ -332,7 +284,7 @@ mod test {
         let c = Cpu::new();
         let (_, c) = c.tick(&CpuInput {
             in_m: Bus16::new(),
-            instruction: Instruction::A(7).bus16(),
+            instruction: Instruction::a(7).unwrap().bus16(),
             reset: Bit::Negative,
         });
         let (_, c) = c.tick(&CpuInput {
@@ -369,4 +321,29 @@ mod test {
         });
         assert_eq!(into_i32(&o.out_m), 20);
     }
+
+    #[test]
+    fn poke_works() {
+        let mut c = Cpu::new();
+        c.poke_data(&make_bus16(3));
+        c.poke_address(&make_bus16(4));
+        c.poke_pc(&make_bus16(5));
+        assert_eq!(into_i32(&c.peek_data()), 3);
+        assert_eq!(into_i32(&c.peek_address()), 4);
+        assert_eq!(into_i32(&c.peek_pc()), 5);
+        let (o, c) = c.tick(&CpuInput {
+            in_m: Bus16::new(),
+            instruction: Instruction::C(Computation {
+                comp: (CompReg::A, Comp::DPlusA),
+                dest: Dest::D,
+                jump: Jump::None,
+            })
+            .bus16(),
+            reset: Bit::Negative,
+        });
+        assert_eq!(into_i32(&o.out_m), 7);
+        assert_eq!(into_i32(&o.address_m), 4);
+        assert_eq!(into_i32(&c.peek_data()), 7);
+        assert_eq!(into_i32(&c.peek_pc()), 6);
+    }
 }
@@ -0,0 +1,405 @@
+use std::fmt;
+
+use crate::gates::bus16::{into_i32, make_bus16};
+use crate::general::Zero;
+use crate::infrastructure::sequential::SequentialCircuit;
+use crate::instruction::{Address, Comp, CompReg, Computation, Dest, Instruction, Jump};
+use crate::primitive::Bit;
+
+use super::{Cpu, CpuInput};
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct CpuState {
+    pub a: u16,
+    pub d: u16,
+    pub pc: u16,
+}
+
+// what a CPU did in one cycle; out_m only means something while write_m is set
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct CpuStep {
+    state: CpuState,
+    write_m: bool,
+    address_m: u16,
+    out_m: u16,
+}
+
+// the Hack ISA as the book describes it, written without the instruction encoding
+// or the ALU control bits so that a mistake there cannot hide in both models
+fn reference(s: CpuState, inst: &Instruction, in_m: u16, reset: bool) -> CpuStep {
+    let next = |pc: u16| if reset { 0 } else { pc };
+    let c = match inst {
+        Instruction::A(a) => {
+            return CpuStep {
+                state: CpuState {
+                    a: a.value() as u16,
+                    d: s.d,
+                    pc: next(s.pc.wrapping_add(1)),
+                },
+                write_m: false,
+                address_m: s.a,
+                out_m: 0,
+            }
+        }
+        Instruction::C(c) => c,
+    };
+    let (d, y) = (s.d, if c.comp.0 == CompReg::M { in_m } else { s.a });
+    let out = match c.comp.1 {
+        Comp::Zero => 0,
+        Comp::One => 1,
+        Comp::MinusOne => 0xffff,
+        Comp::D => d,
+        Comp::A => y,
+        Comp::NotD => !d,
+        Comp::NotA => !y,
+        Comp::MinusD => d.wrapping_neg(),
+        Comp::MinusA => y.wrapping_neg(),
+        Comp::DPlus1 => d.wrapping_add(1),
+        Comp::APlus1 => y.wrapping_add(1),
+        Comp::DMinus1 => d.wrapping_sub(1),
+        Comp::AMinus1 => y.wrapping_sub(1),
+        Comp::DPlusA => d.wrapping_add(y),
+        Comp::DMinusA => d.wrapping_sub(y),
+        Comp::AMinusD => y.wrapping_sub(d),
+        Comp::DAndA => d & y,
+        Comp::DOrA => d | y,
+    };
+    let (to_a, to_d, to_m) = match c.dest {
+        Dest::None => (false, false, false),
+        Dest::M => (false, false, true),
+        Dest::D => (false, true, false),
+        Dest::DM => (false, true, true),
+        Dest::A => (true, false, false),
+        Dest::AM => (true, false, true),
+        Dest::AD => (true, true, false),
+        Dest::ADM => (true, true, true),
+    };
+    let v = out as i16;
+    let jump = match c.jump {
+        Jump::None => false,
+        Jump::Gt => v > 0,
+        Jump::Eq => v == 0,
+        Jump::Ge => v >= 0,
+        Jump::Lt => v < 0,
+        Jump::Ne | Jump::NEq => v != 0,
+        Jump::Le => v <= 0,
+        Jump::Always => true,
+    };
+    CpuStep {
+        state: CpuState {
+            a: if to_a { out } else { s.a },
+            d: if to_d { out } else { s.d },
+            pc: next(if jump { s.a } else { s.pc.wrapping_add(1) }),
+        },
+        write_m: to_m,
+        address_m: s.a,
+        out_m: out,
+    }
+}
+
+fn gates(cpu: &Cpu, inst: &Instruction, in_m: u16, reset: bool) -> (CpuStep, Cpu) {
+    let (o, cpu) = cpu.tick(&CpuInput {
+        in_m: make_bus16(in_m as i32),
+        instruction: inst.bus16(),
+        reset: if reset { Bit::Positive } else { Bit::Negative },
+    });
+    let step = CpuStep {
+        state: CpuState {
+            a: into_i32(&cpu.peek_address()) as u16,
+            d: into_i32(&cpu.peek_data()) as u16,
+            pc: into_i32(&cpu.peek_pc()) as u16,
+        },
+        write_m: matches!(o.write_m, Bit::Positive),
+        address_m: into_i32(&o.address_m) as u16,
+        out_m: into_i32(&o.out_m) as u16,
+    };
+    (step, cpu)
+}
+
+// the first cycle on which the gate-level CPU and the reference disagree
+#[derive(Debug, Eq, PartialEq)]
+pub struct Divergence {
+    pub cycle: u64,
+    pub before: CpuState,
+    pub instruction: String,
+    pub word: u16,
+    pub field: &'static str,
+    pub gates: i32,
+    pub reference: i32,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "cycle {}: `{}` ({:016b}) with A={} D={} PC={}: {} is {} on gates but {} in the reference",
+            self.cycle,
+            self.instruction,
+            self.word,
+            self.before.a as i16,
+            self.before.d as i16,
+            self.before.pc,
+            self.field,
+            self.gates,
+            self.reference
+        )
+    }
+}
+
+fn compare(g: &CpuStep, r: &CpuStep) -> Option<(&'static str, i32, i32)> {
+    let fields = [
+        ("write_m", g.write_m as i32, r.write_m as i32),
+        ("address_m", g.address_m as i32, r.address_m as i32),
+        ("out_m", g.out_m as i16 as i32, r.out_m as i16 as i32),
+        ("A", g.state.a as i16 as i32, r.state.a as i16 as i32),
+        ("D", g.state.d as i16 as i32, r.state.d as i16 as i32),
+        ("PC", g.state.pc as i32, r.state.pc as i32),
+    ];
+    fields
+        .iter()
+        .filter(|(name, _, _)| *name != "out_m" || g.write_m || r.write_m)
+        .find(|(_, g, r)| g != r)
+        .copied()
+}
+
+// runs both CPUs for `cycles` cycles over a shared 32K RAM.
+// `reset` tells on which cycles the reset line is held.
+pub fn lockstep(
+    program: &[Instruction],
+    cycles: u64,
+    mut reset: impl FnMut(u64) -> bool,
+) -> Result<(), Divergence> {
+    let mut ram = vec![0u16; 0x8000];
+    let mut cpu = Cpu::new();
+    let mut state = CpuState::default();
+    let nop = Instruction::a(0).unwrap();
+    for cycle in 0..cycles {
+        let inst = program.get((state.pc & 0x7fff) as usize).unwrap_or(&nop);
+        let in_m = ram[(state.a & 0x7fff) as usize];
+        let r = reset(cycle);
+        let (g, next_cpu) = gates(&cpu, inst, in_m, r);
+        let expected = reference(state, inst, in_m, r);
+        if let Some((field, gates, reference)) = compare(&g, &expected) {
+            return Err(Divergence {
+                cycle,
+                before: state,
+                instruction: inst.to_string(),
+                word: into_i32(&inst.bus16()) as u16,
+                field,
+                gates,
+                reference,
+            });
+        }
+        if g.write_m {
+            ram[(g.address_m & 0x7fff) as usize] = g.out_m;
+        }
+        cpu = next_cpu;
+        state = g.state;
+    }
+    Ok(())
+}
+
+// xorshift64, so that a failing seed can be replayed
+pub struct XorShift(u64);
+
+impl XorShift {
+    pub fn of(seed: u64) -> Self {
+        XorShift(if seed == 0 { 0x9e37_79b9_7f4a_7c15 } else { seed })
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+
+    fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[(self.next_u64() % items.len() as u64) as usize]
+    }
+}
+
+const COMPS: [Comp; 18] = [
+    Comp::Zero,
+    Comp::One,
+    Comp::MinusOne,
+    Comp::D,
+    Comp::A,
+    Comp::NotD,
+    Comp::NotA,
+    Comp::MinusD,
+    Comp::MinusA,
+    Comp::DPlus1,
+    Comp::APlus1,
+    Comp::DMinus1,
+    Comp::AMinus1,
+    Comp::DPlusA,
+    Comp::DMinusA,
+    Comp::AMinusD,
+    Comp::DAndA,
+    Comp::DOrA,
+];
+
+const DESTS: [Dest; 8] = [
+    Dest::None,
+    Dest::M,
+    Dest::D,
+    Dest::DM,
+    Dest::A,
+    Dest::AM,
+    Dest::AD,
+    Dest::ADM,
+];
+
+const JUMPS: [Jump; 9] = [
+    Jump::None,
+    Jump::Gt,
+    Jump::Eq,
+    Jump::Ge,
+    Jump::Lt,
+    Jump::Ne,
+    Jump::NEq,
+    Jump::Le,
+    Jump::Always,
+];
+
+// every encodable instruction can come up; A-instructions favour small
+// addresses so that the program jumps around itself and reuses RAM
+pub fn random_program(rng: &mut XorShift, length: usize) -> Vec<Instruction> {
+    (0..length)
+        .map(|_| {
+            if rng.next_u64().is_multiple_of(2) {
+                let value = match rng.next_u64() % 4 {
+                    0 => rng.next_u64() % 0x8000,
+                    _ => rng.next_u64() % length.max(1) as u64,
+                };
+                return Instruction::A(Address::of(value as i32).unwrap());
+            }
+            let comp = rng.pick(&COMPS);
+            let has_m = !matches!(
+                comp,
+                Comp::Zero
+                    | Comp::One
+                    | Comp::MinusOne
+                    | Comp::D
+                    | Comp::NotD
+                    | Comp::MinusD
+                    | Comp::DPlus1
+                    | Comp::DMinus1
+            );
+            let reg = if has_m && rng.next_u64().is_multiple_of(2) {
+                CompReg::M
+            } else {
+                CompReg::A
+            };
+            Instruction::C(Computation {
+                comp: (reg, comp),
+                dest: rng.pick(&DESTS),
+                jump: rng.pick(&JUMPS),
+            })
+        })
+        .collect()
+}
+
+// a random program of `length` instructions run for `cycles` cycles,
+// with the reset line raised about once every 100 cycles
+pub fn fuzz(seed: u64, length: usize, cycles: u64) -> Result<(), Divergence> {
+    let mut rng = XorShift::of(seed);
+    let program = random_program(&mut rng, length);
+    lockstep(&program, cycles, |_| rng.next_u64().is_multiple_of(100))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembly::assemble;
+
+    #[test]
+    fn reference_works() {
+        let s = CpuState { a: 5, d: 3, pc: 10 };
+        let c = |comp, dest, jump| {
+            Instruction::C(Computation {
+                comp,
+                dest,
+                jump,
+            })
+        };
+        let i = c((CompReg::M, Comp::DMinusA), Dest::AM, Jump::Lt);
+        let r = reference(s, &i, 7, false);
+        assert_eq!(r.state, CpuState { a: 0xfffc, d: 3, pc: 5 });
+        assert_eq!((r.write_m, r.address_m, r.out_m), (true, 5, 0xfffc));
+        let i = c((CompReg::A, Comp::AMinus1), Dest::D, Jump::Gt);
+        assert_eq!(reference(s, &i, 0, false).state, CpuState { a: 5, d: 4, pc: 5 });
+        let i = Instruction::a(42).unwrap();
+        assert_eq!(reference(s, &i, 0, true).state, CpuState { a: 42, d: 3, pc: 0 });
+    }
+
+    #[test]
+    fn lockstep_works() {
+        let code = "@10\nD=A\n@16\nM=D\n(LOOP)\n@16\nMD=M-1\n@LOOP\nD;JGT\n(END)\n@END\n0;JMP\n";
+        let program: Vec<Instruction> = assemble(code)
+            .unwrap()
+            .iter()
+            .map(|w| Instruction::decode(w).unwrap())
+            .collect();
+        assert_eq!(lockstep(&program, 200, |c| c == 150), Ok(()));
+    }
+
+    #[test]
+    fn divergence_is_found() {
+        // as if D|A were encoded with the control bits of D+1
+        let c = |comp| {
+            Instruction::C(Computation {
+                comp: (CompReg::A, comp),
+                dest: Dest::D,
+                jump: Jump::None,
+            })
+        };
+        let mut cpu = Cpu::new();
+        cpu.poke_address(&make_bus16(4));
+        cpu.poke_data(&make_bus16(1));
+        let (g, _) = gates(&cpu, &c(Comp::DPlus1), 0, false);
+        let r = reference(CpuState { a: 4, d: 1, pc: 0 }, &c(Comp::DOrA), 0, false);
+        assert_eq!(compare(&g, &r), Some(("D", 2, 5)));
+        let r = reference(CpuState { a: 4, d: 1, pc: 0 }, &c(Comp::DPlus1), 0, false);
+        assert_eq!(compare(&g, &r), None);
+    }
+
+    #[test]
+    fn fuzz_finds_no_divergence() {
+        for seed in 1..=4 {
+            if let Err(d) = fuzz(seed, 64, 500) {
+                panic!("seed {}: {}", seed, d);
+            }
+        }
+    }
+
+    #[test]
+    fn xor_shift_is_deterministic() {
+        let mut a = XorShift::of(7);
+        let mut b = XorShift::of(7);
+        let xs: Vec<u64> = (0..5).map(|_| a.next_u64()).collect();
+        assert_eq!(xs, (0..5).map(|_| b.next_u64()).collect::<Vec<_>>());
+        assert_ne!(XorShift::of(0).next_u64(), 0);
+    }
+
+    #[test]
+    fn divergence_display_works() {
+        let d = Divergence {
+            cycle: 3,
+            before: CpuState { a: 5, d: 0xffff, pc: 2 },
+            instruction: String::from("M=M+1"),
+            word: 0b1111_1101_1100_1000,
+            field: "out_m",
+            gates: 6,
+            reference: 7,
+        };
+        assert_eq!(
+            d.to_string(),
+            "cycle 3: `M=M+1` (1111110111001000) with A=5 D=-1 PC=2: \
+             out_m is 6 on gates but 7 in the reference"
+        );
+    }
+}
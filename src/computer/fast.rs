@@ -0,0 +1,402 @@
+use std::collections::BTreeSet;
+
+use crate::gates::bus16::{into_i32, Bus16};
+use crate::general::Zero;
+use crate::infrastructure::sequential::MutSC;
+use crate::keyboard::Keyboard;
+
+use super::history::{Delta, History, LastWrite};
+use super::interface::{check_program_size, Computer};
+use super::snapshot::Snapshot;
+use super::trace::{TraceEntry, Tracer};
+
+const KEYBOARD: usize = 0x6000;
+
+// the same machine as MutComputer, computed on plain words instead of gates.
+// addresses keep 15 bits as the address buses do, while A and PC keep all 16.
+pub struct FastComputer {
+    a: u16,
+    d: u16,
+    pc: u16,
+    // RAM and screen; the keyboard register is `key`
+    ram: Box<[u16; KEYBOARD]>,
+    rom: Box<[u16; 0x8000]>,
+    key: u16,
+    keyboard: Keyboard,
+    breakpoints: BTreeSet<u16>,
+    cycles: u64,
+    tracer: Option<Tracer>,
+    // (A, D, PC) before each cycle
+    history: Option<History<(u16, u16, u16)>>,
+}
+
+impl Computer for FastComputer {
+    fn of(instructions: &[Bus16]) -> Result<Self, String> {
+        check_program_size(instructions)?;
+        let mut c = FastComputer {
+            a: 0,
+            d: 0,
+            pc: 0,
+            ram: Box::new([0; KEYBOARD]),
+            rom: Box::new([0; 0x8000]),
+            key: 0,
+            keyboard: Keyboard::new(),
+            breakpoints: BTreeSet::new(),
+            cycles: 0,
+            tracer: None,
+            history: None,
+        };
+        for (i, inst) in instructions.iter().enumerate() {
+            c.rom[i] = into_i32(inst) as u16;
+        }
+        Ok(c)
+    }
+
+    fn of_snapshot(s: &Snapshot) -> Self {
+        let mut c = FastComputer::of(&[]).unwrap();
+        c.rom.copy_from_slice(&s.rom);
+        c.ram.copy_from_slice(&s.ram);
+        c.a = s.a;
+        c.d = s.d;
+        c.pc = s.pc;
+        c.key = s.key;
+        c.keyboard = Keyboard::of_state(s.keyboard_cycle, s.key, s.key_events.clone());
+        c.cycles = s.cycles;
+        c
+    }
+
+    fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    fn trace(&mut self, tracer: Tracer) {
+        self.tracer = Some(tracer);
+    }
+
+    fn stop_trace(&mut self) -> Option<Tracer> {
+        self.tracer.take()
+    }
+
+    fn keep_history(&mut self, capacity: usize) {
+        self.history = if capacity == 0 {
+            None
+        } else {
+            Some(History::of(capacity))
+        };
+    }
+
+    fn history_len(&self) -> usize {
+        self.history.as_ref().map_or(0, History::len)
+    }
+
+    fn step_back(&mut self) -> bool {
+        let delta = match self.history.as_mut().and_then(History::pop) {
+            Some(d) => d,
+            None => return false,
+        };
+        let (a, d, pc) = delta.cpu;
+        self.a = a;
+        self.d = d;
+        self.pc = pc;
+        self.keyboard.rewind(delta.key, delta.key_event);
+        self.key = delta.key;
+        if let Some((addr, before, _)) = delta.write {
+            self.poke_ram(addr, before);
+        }
+        self.cycles = delta.cycle;
+        true
+    }
+
+    fn last_write(&self, addr: u16) -> Option<LastWrite> {
+        self.history.as_ref().and_then(|h| h.last_write(addr))
+    }
+
+    fn step(&mut self) -> TraceEntry {
+        let entry = self.cycle();
+        if let Some(t) = &mut self.tracer {
+            t.record(&entry);
+        }
+        entry
+    }
+
+    fn add_breakpoint(&mut self, pc: u16) {
+        self.breakpoints.insert(pc);
+    }
+
+    fn remove_breakpoint(&mut self, pc: u16) {
+        self.breakpoints.remove(&pc);
+    }
+
+    fn breakpoints(&self) -> Box<dyn Iterator<Item = u16> + '_> {
+        Box::new(self.breakpoints.iter().copied())
+    }
+
+    fn peek_a(&self) -> i16 {
+        self.a as i16
+    }
+
+    fn poke_a(&mut self, value: i16) {
+        self.a = value as u16;
+    }
+
+    fn peek_d(&self) -> i16 {
+        self.d as i16
+    }
+
+    fn poke_d(&mut self, value: i16) {
+        self.d = value as u16;
+    }
+
+    fn peek_pc(&self) -> u16 {
+        self.pc
+    }
+
+    fn poke_pc(&mut self, value: u16) {
+        self.pc = value;
+    }
+
+    fn peek_ram(&mut self, addr: u16) -> i16 {
+        self.read(addr) as i16
+    }
+
+    // the keyboard is read only and nothing lives above it
+    fn poke_ram(&mut self, addr: u16, value: i16) {
+        if let Some(w) = self.ram.get_mut((addr & 0x7fff) as usize) {
+            *w = value as u16;
+        }
+    }
+
+    fn peek_rom(&mut self, addr: u16) -> u16 {
+        self.rom[(addr & 0x7fff) as usize]
+    }
+
+    fn poke_rom(&mut self, addr: u16, value: u16) {
+        self.rom[(addr & 0x7fff) as usize] = value;
+    }
+
+    fn keyboard(&mut self) -> &mut Keyboard {
+        &mut self.keyboard
+    }
+
+    fn reset(&mut self) {
+        self.pc = 0;
+    }
+}
+
+impl FastComputer {
+    fn read(&self, addr: u16) -> u16 {
+        match (addr & 0x7fff) as usize {
+            a if a < KEYBOARD => self.ram[a],
+            KEYBOARD => self.key,
+            _ => 0,
+        }
+    }
+
+    // an instruction as the CPU chip decodes it; see the CPU in cpu.rs
+    fn cycle(&mut self) -> TraceEntry {
+        let (key_before, key_event) = (self.keyboard.key(), self.keyboard.pending());
+        self.key = self.keyboard.tick(&());
+        let (a, d, pc) = (self.a, self.d, self.pc);
+        let inst = self.rom[(pc & 0x7fff) as usize];
+        let mut write = None;
+        if inst & 0x8000 == 0 {
+            self.a = inst;
+            self.pc = pc.wrapping_add(1);
+        } else {
+            let bit = |n: u16| inst & (1 << n) != 0;
+            let mut x = d;
+            let mut y = if bit(12) { self.read(a) } else { a };
+            if bit(11) {
+                x = 0;
+            }
+            if bit(10) {
+                x = !x;
+            }
+            if bit(9) {
+                y = 0;
+            }
+            if bit(8) {
+                y = !y;
+            }
+            let mut out = if bit(7) { x.wrapping_add(y) } else { x & y };
+            if bit(6) {
+                out = !out;
+            }
+            if bit(3) {
+                write = Some((a & 0x7fff, out as i16));
+            }
+            if bit(5) {
+                self.a = out;
+            }
+            if bit(4) {
+                self.d = out;
+            }
+            let ng = out & 0x8000 != 0;
+            let zr = out == 0;
+            let jump = (bit(2) && ng) || (bit(1) && zr) || (bit(0) && !ng && !zr);
+            self.pc = if jump { a } else { pc.wrapping_add(1) };
+        }
+        let before = write.map(|(addr, value)| {
+            let before = self.read(addr) as i16;
+            self.poke_ram(addr, value);
+            before
+        });
+        if let Some(h) = &mut self.history {
+            h.push(Delta {
+                cycle: self.cycles,
+                pc,
+                cpu: (a, d, pc),
+                key: key_before,
+                key_event,
+                write: write.map(|(addr, value)| (addr, before.unwrap(), value)),
+            });
+        }
+        let entry = TraceEntry {
+            cycle: self.cycles,
+            pc,
+            instruction: inst,
+            a: (a as i16, self.a as i16),
+            d: (d as i16, self.d as i16),
+            write,
+        };
+        self.cycles += 1;
+        entry
+    }
+}
+
+impl MutSC for FastComputer {
+    type Input = ();
+    type Output = ();
+
+    fn tick(&mut self, _: &Self::Input) -> Self::Output {
+        let entry = self.cycle();
+        if let Some(t) = &mut self.tracer {
+            t.record(&entry);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembly::assemble;
+    use crate::computer::{HaltReason, MutComputer};
+
+    // a program that exercises every computation, jump and destination
+    const CODE: &str = "@7
+D=A
+@16
+M=D
+@3
+D=D-A
+@17
+M=D
+D=!D
+D=-D
+D=D+1
+D=D-1
+@16
+D=D&M
+D=D|M
+AM=M+1
+AMD=M-1
+D=A-D
+D=-1
+@18
+M=D
+MD=!M
+M=-M
+@19
+M=1
+M=0
+@J1
+D;JLT
+(J1)
+@J2
+D;JEQ
+(J2)
+@J3
+D;JGT
+(J3)
+D=-1
+@J4
+D;JNE
+(J4)
+@J5
+D;JLE
+(J5)
+@J6
+D;JGE
+(J6)
+@J7
+0;JMP
+(J7)
+(END)
+@END
+0;JMP
+";
+
+    fn run<C: Computer>(c: &mut C) -> Vec<TraceEntry> {
+        let mut entries = Vec::new();
+        while !c.is_halted() {
+            entries.push(c.step());
+        }
+        entries
+    }
+
+    #[test]
+    fn matches_gates() {
+        let bin = assemble(CODE).unwrap();
+        let mut slow = MutComputer::of(&bin).unwrap();
+        let mut fast = FastComputer::of(&bin).unwrap();
+        assert_eq!(run(&mut fast), run(&mut slow));
+        for addr in 0..32 {
+            assert_eq!(fast.peek_ram(addr), slow.peek_ram(addr));
+        }
+    }
+
+    #[test]
+    fn memory_map_works() {
+        let mut c = FastComputer::of(&[]).unwrap();
+        c.poke_ram(0x5fff, 7);
+        c.poke_ram(0x6000, 7);
+        c.poke_ram(0x6001, 7);
+        assert_eq!(c.peek_ram(0x5fff), 7);
+        assert_eq!(c.peek_ram(0x6000), 0);
+        assert_eq!(c.peek_ram(0x6001), 0);
+        assert_eq!(c.peek_ram(0xdfff), 7);
+        c.keyboard().press(65);
+        c.tick(&());
+        assert_eq!(c.peek_ram(0x6000), 65);
+    }
+
+    #[test]
+    fn keyboard_and_history_work() {
+        let code = "(LOOP)\n@16\nM=M+1\n@KBD\nD=M\n@17\nM=D\n@LOOP\n0;JMP\n";
+        let mut c = FastComputer::of(&assemble(code).unwrap()).unwrap();
+        c.keep_history(100);
+        c.keyboard().schedule(10, 65);
+        c.run_until_halt(30);
+        assert_eq!((c.peek_ram(16), c.peek_ram(17)), (4, 65));
+        assert_eq!(c.last_write(17).map(|w| (w.cycle, w.after)), Some((29, 65)));
+        c.add_breakpoint(0);
+        assert_eq!(c.reverse_continue(100), (6, HaltReason::Breakpoint));
+        assert_eq!((c.cycles(), c.peek_ram(16)), (24, 3));
+        while c.step_back() {}
+        assert_eq!((c.cycles(), c.peek_ram(16), c.peek_ram(17)), (0, 0, 0));
+        c.remove_breakpoint(0);
+        c.run_until_halt(30);
+        assert_eq!((c.peek_ram(16), c.peek_ram(17)), (4, 65));
+    }
+
+    #[test]
+    fn snapshot_works() {
+        let code = "(LOOP)\n@16\nM=M+1\n@LOOP\n0;JMP\n";
+        let mut slow = MutComputer::of(&assemble(code).unwrap()).unwrap();
+        slow.run_until_halt(10);
+        let mut fast = FastComputer::of_snapshot(&slow.snapshot());
+        fast.run_until_halt(10);
+        slow.run_until_halt(10);
+        assert_eq!(fast.snapshot(), slow.snapshot());
+    }
+}
@@ -0,0 +1,107 @@
+use std::collections::VecDeque;
+
+// what a cycle replaced, enough to undo it.
+// S is the CPU state before the cycle; the gate-level CPU is a persistent
+// SequentialCircuit, so its old state is kept as it is.
+pub(crate) struct Delta<S> {
+    pub cycle: u64,
+    pub pc: u16,
+    pub cpu: S,
+    pub key: u16,
+    pub key_event: Option<u16>,
+    // (address, before, after)
+    pub write: Option<(u16, i16, i16)>,
+}
+
+// the most recent cycle that wrote to an address
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LastWrite {
+    pub cycle: u64,
+    pub pc: u16,
+    pub before: i16,
+    pub after: i16,
+}
+
+// the last `capacity` cycles; older ones are forgotten
+pub(crate) struct History<S> {
+    capacity: usize,
+    deltas: VecDeque<Delta<S>>,
+}
+
+impl<S> History<S> {
+    pub fn of(capacity: usize) -> Self {
+        History {
+            capacity,
+            deltas: VecDeque::with_capacity(capacity.min(1 << 16)),
+        }
+    }
+
+    pub fn push(&mut self, delta: Delta<S>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.deltas.len() == self.capacity {
+            self.deltas.pop_front();
+        }
+        self.deltas.push_back(delta);
+    }
+
+    pub fn pop(&mut self) -> Option<Delta<S>> {
+        self.deltas.pop_back()
+    }
+
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn last_write(&self, address: u16) -> Option<LastWrite> {
+        self.deltas.iter().rev().find_map(|d| match d.write {
+            Some((a, before, after)) if a == address => Some(LastWrite {
+                cycle: d.cycle,
+                pc: d.pc,
+                before,
+                after,
+            }),
+            _ => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delta(cycle: u64, write: Option<(u16, i16, i16)>) -> Delta<()> {
+        Delta {
+            cycle,
+            pc: cycle as u16 + 10,
+            cpu: (),
+            key: 0,
+            key_event: None,
+            write,
+        }
+    }
+
+    #[test]
+    fn history_works() {
+        let mut h = History::of(3);
+        h.push(delta(0, Some((5, 0, 1))));
+        h.push(delta(1, Some((6, 0, 2))));
+        h.push(delta(2, Some((5, 1, 3))));
+        assert_eq!(
+            h.last_write(5),
+            Some(LastWrite {
+                cycle: 2,
+                pc: 12,
+                before: 1,
+                after: 3
+            })
+        );
+        h.push(delta(3, None));
+        assert_eq!(h.len(), 3);
+        assert_eq!(h.pop().map(|d| d.cycle), Some(3));
+        assert_eq!(h.pop().map(|d| d.cycle), Some(2));
+        assert_eq!(h.last_write(5), None);
+        assert_eq!(h.last_write(6).map(|w| w.after), Some(2));
+    }
+}
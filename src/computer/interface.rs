@@ -0,0 +1,170 @@
+use crate::gates::bus16::{make_bus16, Bus16};
+use crate::infrastructure::sequential::MutSC;
+use crate::instruction::{Dest, Instruction, Jump};
+use crate::keyboard::Keyboard;
+use crate::screen::Screen;
+
+use super::history::LastWrite;
+use super::snapshot::{Snapshot, SNAPSHOT_RAM_WORDS, SNAPSHOT_ROM_WORDS};
+use super::trace::{TraceEntry, Tracer};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HaltReason {
+    Halted,
+    CycleLimit,
+    Breakpoint,
+    // stepping back reached the oldest recorded cycle
+    HistoryExhausted,
+}
+
+// every Computer::of goes through this, so no computer drops or wraps extra words
+pub(super) fn check_program_size(instructions: &[Bus16]) -> Result<(), String> {
+    if instructions.len() > SNAPSHOT_ROM_WORDS {
+        return Err(format!(
+            "program has {} words, more than the {} ROM holds",
+            instructions.len(),
+            SNAPSHOT_ROM_WORDS
+        ));
+    }
+    Ok(())
+}
+
+// what tooling needs from a Hack computer, whether it is built of gates or not.
+// a tick runs one instruction.
+pub trait Computer: MutSC<Input = (), Output = ()> {
+    // fails when the program does not fit in ROM
+    fn of(instructions: &[Bus16]) -> Result<Self, String>
+    where
+        Self: Sized;
+
+    // breakpoints and the tracer are not part of the machine and are not restored
+    fn of_snapshot(s: &Snapshot) -> Self
+    where
+        Self: Sized;
+
+    // cycles ticked since construction
+    fn cycles(&self) -> u64;
+
+    // every following cycle is recorded until stop_trace
+    fn trace(&mut self, tracer: Tracer);
+
+    fn stop_trace(&mut self) -> Option<Tracer>;
+
+    // remembers the last `capacity` cycles so that they can be undone; 0 forgets everything
+    fn keep_history(&mut self, capacity: usize);
+
+    // how many cycles step_back can undo
+    fn history_len(&self) -> usize;
+
+    // undoes the last cycle; false when there is no history left
+    fn step_back(&mut self) -> bool;
+
+    // only cycles still in the history are searched
+    fn last_write(&self, addr: u16) -> Option<LastWrite>;
+
+    // ticks once and tells what happened
+    fn step(&mut self) -> TraceEntry;
+
+    fn add_breakpoint(&mut self, pc: u16);
+
+    fn remove_breakpoint(&mut self, pc: u16);
+
+    fn breakpoints(&self) -> Box<dyn Iterator<Item = u16> + '_>;
+
+    fn peek_a(&self) -> i16;
+
+    fn poke_a(&mut self, value: i16);
+
+    fn peek_d(&self) -> i16;
+
+    fn poke_d(&mut self, value: i16);
+
+    fn peek_pc(&self) -> u16;
+
+    fn poke_pc(&mut self, value: u16);
+
+    fn peek_ram(&mut self, addr: u16) -> i16;
+
+    fn poke_ram(&mut self, addr: u16, value: i16);
+
+    fn peek_rom(&mut self, addr: u16) -> u16;
+
+    fn poke_rom(&mut self, addr: u16, value: u16);
+
+    fn keyboard(&mut self) -> &mut Keyboard;
+
+    // like the reset button: PC goes back to 0, registers and RAM are kept
+    fn reset(&mut self);
+
+    fn snapshot(&mut self) -> Snapshot {
+        let keyboard = self.keyboard();
+        let (keyboard_cycle, key) = (keyboard.cycle(), keyboard.key());
+        let key_events = keyboard.scheduled().collect();
+        Snapshot {
+            cycles: self.cycles(),
+            a: self.peek_a() as u16,
+            d: self.peek_d() as u16,
+            pc: self.peek_pc(),
+            keyboard_cycle,
+            key,
+            key_events,
+            ram: (0..SNAPSHOT_RAM_WORDS)
+                .map(|addr| self.peek_ram(addr as u16) as u16)
+                .collect(),
+            rom: (0..SNAPSHOT_ROM_WORDS)
+                .map(|addr| self.peek_rom(addr as u16))
+                .collect(),
+        }
+    }
+
+    fn screen(&mut self) -> Screen {
+        let words = (0x4000..0x4000 + Screen::WORDS as u16)
+            .map(|addr| self.peek_ram(addr) as u16)
+            .collect();
+        Screen::of(words)
+    }
+
+    // runs until the program halts, hits a breakpoint or runs max_cycles cycles.
+    // a breakpoint at the starting PC is ignored so that the run can be resumed.
+    fn run_until_halt(&mut self, max_cycles: u64) -> (u64, HaltReason) {
+        for cycle in 0..max_cycles {
+            let pc = self.peek_pc();
+            if cycle > 0 && self.breakpoints().any(|b| b == pc) {
+                return (cycle, HaltReason::Breakpoint);
+            }
+            if self.is_halted() {
+                return (cycle, HaltReason::Halted);
+            }
+            self.tick(&());
+        }
+        (max_cycles, HaltReason::CycleLimit)
+    }
+
+    // steps back until a breakpoint is reached, like run_until_halt in reverse
+    fn reverse_continue(&mut self, max_cycles: u64) -> (u64, HaltReason) {
+        for cycle in 0..max_cycles {
+            if !self.step_back() {
+                return (cycle, HaltReason::HistoryExhausted);
+            }
+            let pc = self.peek_pc();
+            if self.breakpoints().any(|b| b == pc) {
+                return (cycle + 1, HaltReason::Breakpoint);
+            }
+        }
+        (max_cycles, HaltReason::CycleLimit)
+    }
+
+    // PC is at `@p` at address p followed by an unconditional jump that writes nothing,
+    // so the program loops over those two instructions forever
+    fn is_halted(&mut self) -> bool {
+        let pc = self.peek_pc();
+        // `@p` is the word p itself; checked first as it rarely holds
+        if self.peek_rom(pc) != pc {
+            return false;
+        }
+        match Instruction::decode(&make_bus16(self.peek_rom(pc.wrapping_add(1)) as i32)) {
+            Ok(Instruction::C(c)) => c.dest == Dest::None && c.jump == Jump::Always,
+            _ => false,
+        }
+    }
+}
@@ -1,40 +1,260 @@
-use crate::gates::bus16::Bus16;
+use crate::gates::bit::{self, make_bit};
+use crate::gates::bus16::{self, mux4way16, Bus16};
+use crate::gates::bus2::Bus2;
+use crate::gates::bus8::or8way;
 use crate::general::Zero;
-use crate::infrastructure::sequential::MutSC;
+use crate::infrastructure::sequential::{
+    ArrayMSC2, ArraySC2, FeedforwardMSC, FeedforwardMSCDef, FeedforwardSC, FeedforwardSCDef, MutSC,
+    SequentialCircuit, TupleMSC, TupleSC,
+};
 use crate::primitive::Bit;
-use crate::sequential::{MutRam16k, MutRam4k, Ram16kInput, Ram4kInput};
+use crate::sequential::{MutRam16k, MutRam8k, Ram16k, Ram16kInput, Ram8k, Ram8kInput};
 
-// future work: should be constructed of gates
-pub struct MutRom(Box<[MutRam16k; 2]>);
+pub type Rom32k = FeedforwardSC<ArraySC2<Ram16k>, Rom32kImpl>;
+
+pub struct Rom32kImpl;
+
+// the CPU only drives the address; input and load are for the loader
+pub struct Rom32kInput {
+    pub input: Bus16,
+    pub address: [Bit; 15],
+    pub load: Bit,
+}
+
+impl FeedforwardSCDef<ArraySC2<Ram16k>> for Rom32kImpl {
+    type Input = Rom32kInput;
+    type Output = Bus16;
+    type Jump = Bit;
+
+    fn new() -> ArraySC2<Ram16k> {
+        ArraySC2::new()
+    }
+    fn pre(input: &Self::Input) -> ([Ram16kInput; 2], Self::Jump) {
+        let Rom32kInput {
+            input: i,
+            address: a,
+            load,
+        } = input;
+        let r = |load: Bit| Ram16kInput {
+            input: i.clone(),
+            address: low14(a),
+            load,
+        };
+        let [l0, l1] = bit::dmux(*load, a[0]);
+        ([r(l0), r(l1)], a[0])
+    }
+    fn post(b: &[Bus16; 2], jump: &Self::Jump) -> Self::Output {
+        bus16::mux(&b[0], &b[1], *jump)
+    }
+}
+
+// the same circuit of mutable RAM16Ks
+pub type MutRom32k = FeedforwardMSC<ArrayMSC2<MutRam16k>, Rom32kImpl>;
+
+impl FeedforwardMSCDef<ArrayMSC2<MutRam16k>> for Rom32kImpl {
+    type Input = Rom32kInput;
+    type Output = Bus16;
+    type Jump = Bit;
+
+    fn pre(input: &Self::Input) -> ([Ram16kInput; 2], Self::Jump) {
+        <Self as FeedforwardSCDef<ArraySC2<Ram16k>>>::pre(input)
+    }
+    fn post(b: &[Bus16; 2], jump: &Self::Jump) -> Self::Output {
+        <Self as FeedforwardSCDef<ArraySC2<Ram16k>>>::post(b, jump)
+    }
+}
+
+// what the computer needs from its instruction memory
+pub trait Rom: MutSC<Input = [Bit; 15], Output = Bus16> + Zero + Sized {
+    // the CPU cannot write to ROM, only the loader can
+    fn poke(&mut self, addr: i32, value: &Bus16);
+
+    fn of(instructions: &[Bus16]) -> Self {
+        let mut rom = Self::new();
+        for (i, inst) in instructions.iter().enumerate() {
+            rom.poke(i as i32, inst);
+        }
+        rom
+    }
+
+    fn peek(&mut self, addr: i32) -> Bus16 {
+        self.tick(&make_bus15(addr))
+    }
+}
+
+fn rom_input(a: &[Bit; 15], input: &Bus16, load: Bit) -> Rom32kInput {
+    Rom32kInput {
+        input: input.clone(),
+        address: *a,
+        load,
+    }
+}
+
+pub struct MutRom(MutRom32k);
 
 impl MutSC for MutRom {
     type Input = [Bit; 15];
     type Output = Bus16;
 
     fn tick(&mut self, a: &Self::Input) -> Self::Output {
-        let addr_high = a[0];
-        let addr_low = [
-            a[1], a[2], a[3], a[4], a[5], a[6], a[7], a[8], a[9], a[10], a[11], a[12], a[13], a[14],
-        ];
-        let ram16k_input = Ram16kInput {
-            input: Bus16::new(),
-            address: addr_low,
-            load: Bit::Negative,
-        };
-        match addr_high {
-            Bit::Negative => self.0[0].tick(&ram16k_input),
-            Bit::Positive => self.0[1].tick(&ram16k_input),
-        }
+        self.0.tick(&rom_input(a, &Bus16::new(), Bit::Negative))
+    }
+}
+
+impl Zero for MutRom {
+    fn new() -> Self {
+        MutRom(MutRom32k::new())
+    }
+}
+
+impl Rom for MutRom {
+    fn poke(&mut self, addr: i32, value: &Bus16) {
+        self.0
+            .tick(&rom_input(&make_bus15(addr), value, Bit::Positive));
+    }
+}
+
+// Rom32k down to its registers; much slower than MutRom
+pub struct GateRom(Rom32k);
+
+impl MutSC for GateRom {
+    type Input = [Bit; 15];
+    type Output = Bus16;
+
+    fn tick(&mut self, a: &Self::Input) -> Self::Output {
+        self.access(rom_input(a, &Bus16::new(), Bit::Negative))
+    }
+}
+
+impl Zero for GateRom {
+    fn new() -> Self {
+        GateRom(Rom32k::new())
+    }
+}
+
+impl Rom for GateRom {
+    fn poke(&mut self, addr: i32, value: &Bus16) {
+        self.access(rom_input(&make_bus15(addr), value, Bit::Positive));
+    }
+}
+
+impl GateRom {
+    fn access(&mut self, input: Rom32kInput) -> Bus16 {
+        let (o, next) = self.0.tick(&input);
+        self.0 = next;
+        o
     }
 }
 
-// future work: should be constructed of gates
-pub struct MutDataMemory(Box<[MutRam4k; 2]>);
+// RAM at 0x0000-0x3fff, screen at 0x4000-0x5fff, keyboard at 0x6000, as Memory.hdl
+pub type Memory = FeedforwardSC<TupleSC<Ram16k, Ram8k>, MemoryImpl>;
+
+pub struct MemoryImpl;
+
+pub struct MemoryInput {
+    pub input: Bus16,
+    pub load: Bit,
+    pub address: [Bit; 15],
+    // what the keyboard chip outputs
+    pub keyboard: Bus16,
+}
+
+impl FeedforwardSCDef<TupleSC<Ram16k, Ram8k>> for MemoryImpl {
+    type Input = MemoryInput;
+    type Output = Bus16;
+    type Jump = (Bus2, Bus16);
+
+    fn new() -> TupleSC<Ram16k, Ram8k> {
+        TupleSC::new()
+    }
+    fn pre(input: &Self::Input) -> ((Ram16kInput, Ram8kInput), Self::Jump) {
+        let MemoryInput {
+            input: i,
+            load,
+            address: a,
+            keyboard,
+        } = input;
+        let [load_ram, load_io] = bit::dmux(*load, a[0]);
+        let [load_screen, _] = bit::dmux(load_io, a[1]);
+        (
+            (
+                Ram16kInput {
+                    input: i.clone(),
+                    address: low14(a),
+                    load: load_ram,
+                },
+                Ram8kInput {
+                    input: i.clone(),
+                    address: low13(a),
+                    load: load_screen,
+                },
+            ),
+            ([a[0], a[1]], keyboard_out(a, keyboard)),
+        )
+    }
+    fn post(b: &(Bus16, Bus16), jump: &Self::Jump) -> Self::Output {
+        let (ram, screen) = b;
+        let (sel, keyboard) = jump;
+        mux4way16(ram, ram, screen, keyboard, sel)
+    }
+}
+
+// the same circuit of mutable RAMs
+pub type MutMemory = FeedforwardMSC<TupleMSC<MutRam16k, MutRam8k>, MemoryImpl>;
+
+impl FeedforwardMSCDef<TupleMSC<MutRam16k, MutRam8k>> for MemoryImpl {
+    type Input = MemoryInput;
+    type Output = Bus16;
+    type Jump = (Bus2, Bus16);
+
+    fn pre(input: &Self::Input) -> ((Ram16kInput, Ram8kInput), Self::Jump) {
+        <Self as FeedforwardSCDef<TupleSC<Ram16k, Ram8k>>>::pre(input)
+    }
+    fn post(b: &(Bus16, Bus16), jump: &Self::Jump) -> Self::Output {
+        <Self as FeedforwardSCDef<TupleSC<Ram16k, Ram8k>>>::post(b, jump)
+    }
+}
 
 pub struct DataMemoryInput {
     pub input: Bus16,
     pub load: Bit,
-    pub address: [Bit; 13],
+    pub address: [Bit; 15],
+}
+
+// what the computer needs from its data memory
+pub trait DataMemory: MutSC<Input = DataMemoryInput, Output = Bus16> + Zero {
+    fn set_keyboard(&mut self, key: Bus16);
+
+    fn peek(&mut self, addr: i32) -> Bus16 {
+        self.tick(&DataMemoryInput {
+            input: Bus16::new(),
+            address: make_bus15(addr),
+            load: Bit::Negative,
+        })
+    }
+
+    fn poke(&mut self, addr: i32, value: &Bus16) {
+        self.tick(&DataMemoryInput {
+            input: value.clone(),
+            address: make_bus15(addr),
+            load: Bit::Positive,
+        });
+    }
+}
+
+fn memory_input(i: &DataMemoryInput, keyboard: &Bus16) -> MemoryInput {
+    MemoryInput {
+        input: i.input.clone(),
+        load: i.load,
+        address: i.address,
+        keyboard: keyboard.clone(),
+    }
+}
+
+// MutMemory, holding what the keyboard chip outputs
+pub struct MutDataMemory {
+    memory: MutMemory,
+    keyboard: Bus16,
 }
 
 impl MutSC for MutDataMemory {
@@ -42,87 +262,154 @@ impl MutSC for MutDataMemory {
     type Output = Bus16;
 
     fn tick(&mut self, i: &Self::Input) -> Self::Output {
-        let a = &i.address;
-        let addr_high = a[0];
-        let addr_low = [
-            a[1], a[2], a[3], a[4], a[5], a[6], a[7], a[8], a[9], a[10], a[11], a[12],
-        ];
-        let ram4k_input = Ram4kInput {
-            input: i.input.clone(),
-            address: addr_low,
-            load: i.load,
-        };
-        match addr_high {
-            Bit::Negative => self.0[0].tick(&ram4k_input),
-            Bit::Positive => self.0[1].tick(&ram4k_input),
-        }
+        self.memory.tick(&memory_input(i, &self.keyboard))
     }
 }
 
 impl Zero for MutDataMemory {
     fn new() -> Self {
-        MutDataMemory(Box::new([MutRam4k::new(), MutRam4k::new()]))
+        MutDataMemory {
+            memory: MutMemory::new(),
+            keyboard: Bus16::new(),
+        }
     }
 }
 
-pub mod testing {
-    use super::*;
-    use crate::gates::bit::testing::*;
-
-    impl MutRom {
-        pub fn of(instructions: &Vec<Bus16>) -> Self {
-            let mut ram0 = MutRam16k::new();
-            let mut ram1 = MutRam16k::new();
-            for (i, inst) in instructions.iter().enumerate() {
-                let addr = make_bus15(i as i32);
-                let addr14 = [
-                    addr[0x1], addr[0x2], addr[0x3], addr[0x4], addr[0x5], addr[0x6], addr[0x7],
-                    addr[0x8], addr[0x9], addr[0xa], addr[0xb], addr[0xc], addr[0xd], addr[0xe],
-                ];
-                match addr[0] {
-                    Bit::Negative => {
-                        ram0.tick(&Ram16kInput {
-                            input: inst.clone(),
-                            address: addr14,
-                            load: Bit::Positive,
-                        });
-                    }
-                    Bit::Positive => {
-                        ram1.tick(&Ram16kInput {
-                            input: inst.clone(),
-                            address: addr14,
-                            load: Bit::Positive,
-                        });
-                    }
-                }
-            }
-            Self(Box::new([ram0, ram1]))
-        }
+impl DataMemory for MutDataMemory {
+    fn set_keyboard(&mut self, key: Bus16) {
+        self.keyboard = key;
     }
+}
 
-    impl MutDataMemory {
-        pub fn peek(&mut self, addr: i32) -> Bus16 {
-            self.tick(&DataMemoryInput {
-                input: Bus16::new(),
-                address: make_bus13(addr),
-                load: Bit::Negative,
-            })
-        }
+// Memory down to its registers; much slower than MutDataMemory
+pub struct GateDataMemory {
+    memory: Memory,
+    keyboard: Bus16,
+}
+
+impl MutSC for GateDataMemory {
+    type Input = DataMemoryInput;
+    type Output = Bus16;
+
+    fn tick(&mut self, i: &Self::Input) -> Self::Output {
+        let (o, next) = self.memory.tick(&memory_input(i, &self.keyboard));
+        self.memory = next;
+        o
     }
+}
 
-    fn make_bus13(i: i32) -> [Bit; 13] {
-        let mut b13 = [Bit::Negative; 13];
-        for b in 0..13 {
-            b13[b] = make_bit(i & (1 << (12 - b)) != 0);
+impl Zero for GateDataMemory {
+    fn new() -> Self {
+        GateDataMemory {
+            memory: Memory::new(),
+            keyboard: Bus16::new(),
         }
-        b13
     }
+}
+
+impl DataMemory for GateDataMemory {
+    fn set_keyboard(&mut self, key: Bus16) {
+        self.keyboard = key;
+    }
+}
 
-    fn make_bus15(i: i32) -> [Bit; 15] {
-        let mut b15 = [Bit::Negative; 15];
-        for b in 0..15 {
-            b15[b] = make_bit(i & (1 << (14 - b)) != 0);
+// the keyboard is read only and nothing lives above it, so it answers 0x6000 alone
+fn keyboard_out(a: &[Bit; 15], keyboard: &Bus16) -> Bus16 {
+    let above = bit::or(
+        or8way([a[2], a[3], a[4], a[5], a[6], a[7], a[8], a[9]]),
+        or8way([
+            a[10],
+            a[11],
+            a[12],
+            a[13],
+            a[14],
+            Bit::Negative,
+            Bit::Negative,
+            Bit::Negative,
+        ]),
+    );
+    bus16::and(keyboard, &bus16::broadcast(bit::not(above)))
+}
+
+fn low14(a: &[Bit; 15]) -> [Bit; 14] {
+    [
+        a[1], a[2], a[3], a[4], a[5], a[6], a[7], a[8], a[9], a[10], a[11], a[12], a[13], a[14],
+    ]
+}
+
+fn low13(a: &[Bit; 15]) -> [Bit; 13] {
+    [
+        a[2], a[3], a[4], a[5], a[6], a[7], a[8], a[9], a[10], a[11], a[12], a[13], a[14],
+    ]
+}
+
+pub fn make_bus15(i: i32) -> [Bit; 15] {
+    let mut b15 = [Bit::Negative; 15];
+    for b in 0..15 {
+        b15[b] = make_bit(i & (1 << (14 - b)) != 0);
+    }
+    b15
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gates::bus16::{into_i32, make_bus16};
+
+    fn poke<M: DataMemory>(m: &mut M, addr: i32, value: i32) {
+        m.poke(addr, &make_bus16(value));
+    }
+
+    fn memory_map<M: DataMemory>() {
+        let mut m = M::new();
+        let addrs = [0, 1, 0x1000, 0x2000, 0x3fff, 0x4000, 0x5000, 0x5fff];
+        for (i, &addr) in addrs.iter().enumerate() {
+            poke(&mut m, addr, i as i32 + 1);
+        }
+        for (i, &addr) in addrs.iter().enumerate() {
+            assert_eq!(into_i32(&m.peek(addr)), i as i32 + 1, "addr = {:#x}", addr);
         }
-        b15
+        // nothing wraps around
+        assert_eq!(into_i32(&m.peek(0x0800)), 0);
+        assert_eq!(into_i32(&m.peek(0x2800)), 0);
+    }
+
+    #[test]
+    fn memory_map_works() {
+        memory_map::<MutDataMemory>();
+        memory_map::<GateDataMemory>();
+    }
+
+    fn rom<R: Rom>() {
+        let mut rom = R::of(&[make_bus16(7), make_bus16(8)]);
+        rom.poke(0x4001, &make_bus16(9));
+        assert_eq!(into_i32(&rom.peek(0)), 7);
+        assert_eq!(into_i32(&rom.peek(1)), 8);
+        assert_eq!(into_i32(&rom.peek(2)), 0);
+        assert_eq!(into_i32(&rom.peek(0x4001)), 9);
+    }
+
+    #[test]
+    fn rom_works() {
+        rom::<MutRom>();
+        rom::<GateRom>();
+    }
+
+    fn keyboard<M: DataMemory>() {
+        let mut m = M::new();
+        assert_eq!(into_i32(&m.peek(0x6000)), 0);
+        m.set_keyboard(make_bus16(75));
+        assert_eq!(into_i32(&m.peek(0x6000)), 75);
+        poke(&mut m, 0x6000, 1);
+        assert_eq!(into_i32(&m.peek(0x6000)), 75);
+        poke(&mut m, 0x6001, 1);
+        assert_eq!(into_i32(&m.peek(0x6001)), 0);
+        assert_eq!(into_i32(&m.peek(0x7fff)), 0);
+    }
+
+    #[test]
+    fn keyboard_is_read_only() {
+        keyboard::<MutDataMemory>();
+        keyboard::<GateDataMemory>();
     }
 }
@@ -4,10 +4,24 @@ pub use cpu::*;
 mod memory;
 pub use memory::*;
 
+mod interface;
+pub use interface::*;
+
+#[allow(clippy::module_inception)]
 mod computer;
 pub use computer::*;
 
-pub mod testing {
-    pub use super::computer::testing::*;
-    pub use super::cpu::testing::*;
-}
+mod fast;
+pub use fast::*;
+
+mod trace;
+pub use trace::*;
+
+mod snapshot;
+pub use snapshot::*;
+
+mod history;
+pub use history::LastWrite;
+
+mod differential;
+pub use differential::*;
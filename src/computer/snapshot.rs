@@ -0,0 +1,208 @@
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+
+const MAGIC: &[u8; 8] = b"HACKSNAP";
+const VERSION: u16 = 1;
+
+// RAM and screen; the keyboard register is restored from the keyboard
+pub const SNAPSHOT_RAM_WORDS: usize = 0x6000;
+pub const SNAPSHOT_ROM_WORDS: usize = 0x8000;
+
+// everything needed to resume a computer
+// all numbers are little endian and trailing zero words of RAM and ROM are left out:
+//   "HACKSNAP", version: u16, cycles: u64, A: u16, D: u16, PC: u16,
+//   keyboard cycle: u64, key: u16, n: u32, n * (cycle: u64, key: u16),
+//   n: u32, n * RAM word: u16, n: u32, n * ROM word: u16
+// only of_bytes and Computer::snapshot build one, so RAM and ROM always have their full size
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Snapshot {
+    pub(super) cycles: u64,
+    pub(super) a: u16,
+    pub(super) d: u16,
+    pub(super) pc: u16,
+    pub(super) keyboard_cycle: u64,
+    pub(super) key: u16,
+    pub(super) key_events: BTreeMap<u64, u16>,
+    pub(super) ram: Vec<u16>,
+    pub(super) rom: Vec<u16>,
+}
+
+impl Snapshot {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let ram = trim_zeros(&self.ram);
+        let rom = trim_zeros(&self.rom);
+        let mut out = Vec::with_capacity(64 + 2 * (ram.len() + rom.len()));
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        out.extend_from_slice(&self.cycles.to_le_bytes());
+        for r in [self.a, self.d, self.pc] {
+            out.extend_from_slice(&r.to_le_bytes());
+        }
+        out.extend_from_slice(&self.keyboard_cycle.to_le_bytes());
+        out.extend_from_slice(&self.key.to_le_bytes());
+        out.extend_from_slice(&(self.key_events.len() as u32).to_le_bytes());
+        for (cycle, key) in self.key_events.iter() {
+            out.extend_from_slice(&cycle.to_le_bytes());
+            out.extend_from_slice(&key.to_le_bytes());
+        }
+        for words in [ram, rom] {
+            out.extend_from_slice(&(words.len() as u32).to_le_bytes());
+            for w in words {
+                out.extend_from_slice(&w.to_le_bytes());
+            }
+        }
+        out
+    }
+
+    pub fn of_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let mut r = Reader(bytes);
+        if r.take(MAGIC.len())? != MAGIC {
+            return Err(String::from("not a snapshot"));
+        }
+        let version = r.u16()?;
+        if version != VERSION {
+            return Err(format!("unsupported snapshot version {}", version));
+        }
+        let cycles = r.u64()?;
+        let (a, d, pc) = (r.u16()?, r.u16()?, r.u16()?);
+        let keyboard_cycle = r.u64()?;
+        let key = r.u16()?;
+        let mut key_events = BTreeMap::new();
+        for _ in 0..r.u32()? {
+            let cycle = r.u64()?;
+            key_events.insert(cycle, r.u16()?);
+        }
+        let ram = r.words("RAM", SNAPSHOT_RAM_WORDS)?;
+        let rom = r.words("ROM", SNAPSHOT_ROM_WORDS)?;
+        if !r.0.is_empty() {
+            return Err(format!("{} unexpected bytes at the end", r.0.len()));
+        }
+        Ok(Snapshot {
+            cycles,
+            a,
+            d,
+            pc,
+            keyboard_cycle,
+            key,
+            key_events,
+            ram,
+            rom,
+        })
+    }
+}
+
+pub(crate) fn trim_zeros(words: &[u16]) -> &[u16] {
+    let n = words.iter().rposition(|&w| w != 0).map_or(0, |i| i + 1);
+    &words[..n]
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.0.len() < n {
+            return Err(String::from("snapshot is truncated"));
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    // padded with zeros to the full size
+    fn words(&mut self, name: &str, size: usize) -> Result<Vec<u16>, String> {
+        let n = self.u32()? as usize;
+        if n > size {
+            return Err(format!("{} has {} words, more than {}", name, n, size));
+        }
+        let mut words = Vec::with_capacity(size);
+        for _ in 0..n {
+            words.push(self.u16()?);
+        }
+        words.resize(size, 0);
+        Ok(words)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> Snapshot {
+        let mut ram = vec![0; SNAPSHOT_RAM_WORDS];
+        ram[0] = 256;
+        ram[2] = 0xffff;
+        let mut rom = vec![0; SNAPSHOT_ROM_WORDS];
+        rom[0] = 0x0002;
+        rom[1] = 0xec10;
+        Snapshot {
+            cycles: 1234,
+            a: 2,
+            d: 0xfffe,
+            pc: 1,
+            keyboard_cycle: 1234,
+            key: 65,
+            key_events: [(1300, 0), (1400, 128)].iter().cloned().collect(),
+            ram,
+            rom,
+        }
+    }
+
+    #[test]
+    fn round_trip_works() {
+        let s = snapshot();
+        let bytes = s.to_bytes();
+        assert_eq!(&bytes[..10], b"HACKSNAP\x01\x00");
+        // header 38, two key events 20, RAM 4 + 6, ROM 4 + 4
+        assert_eq!(bytes.len(), 76);
+        assert_eq!(Snapshot::of_bytes(&bytes), Ok(s));
+    }
+
+    #[test]
+    fn of_bytes_reports_errors() {
+        let bytes = snapshot().to_bytes();
+        let e = |b: &[u8]| Snapshot::of_bytes(b).err();
+        assert_eq!(e(b"HACKSNIP"), Some(String::from("not a snapshot")));
+        assert_eq!(
+            e(b"HACKSNAP\x02\x00"),
+            Some(String::from("unsupported snapshot version 2"))
+        );
+        assert_eq!(
+            e(&bytes[..bytes.len() - 1]),
+            Some(String::from("snapshot is truncated"))
+        );
+        let mut longer = bytes.clone();
+        longer.push(0);
+        assert_eq!(
+            e(&longer),
+            Some(String::from("1 unexpected bytes at the end"))
+        );
+    }
+
+    // PC keeps all 16 bits, though only 15 of them address ROM
+    #[test]
+    fn high_pc_round_trips() {
+        use crate::computer::{Computer, FastComputer};
+        use crate::infrastructure::sequential::MutSC;
+
+        let bin = crate::assembly::assemble("A=-1\n0;JMP\n").unwrap();
+        let mut c = FastComputer::of(&bin).unwrap();
+        c.tick(&());
+        c.tick(&());
+        assert_eq!(c.peek_pc(), 0xffff);
+        let s = Snapshot::of_bytes(&c.snapshot().to_bytes()).unwrap();
+        assert_eq!(s.pc, 0xffff);
+        assert_eq!(FastComputer::of_snapshot(&s).peek_pc(), 0xffff);
+    }
+}
@@ -0,0 +1,244 @@
+use std::io;
+use std::io::Write;
+
+use crate::assembly::SourceMap;
+use crate::gates::bus16::make_bus16;
+use crate::instruction::{CompReg, Instruction};
+
+// what a single cycle did
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TraceEntry {
+    pub cycle: u64,
+    pub pc: u16,
+    pub instruction: u16,
+    pub a: (i16, i16),
+    pub d: (i16, i16),
+    // (address, value)
+    pub write: Option<(u16, i16)>,
+}
+
+impl TraceEntry {
+    // the address of M if the instruction used it
+    pub fn read(&self) -> Option<u16> {
+        match Instruction::decode(&make_bus16(self.instruction as i32)) {
+            Ok(Instruction::C(c)) if c.comp.0 == CompReg::M => Some(self.a.0 as u16 & 0x7fff),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TraceFormat {
+    Text,
+    JsonLines,
+}
+
+pub struct Tracer {
+    out: Box<dyn Write>,
+    format: TraceFormat,
+    source_map: Option<SourceMap>,
+    error: Option<io::Error>,
+}
+
+impl Tracer {
+    pub fn of(out: Box<dyn Write>, format: TraceFormat) -> Self {
+        Tracer {
+            out,
+            format,
+            source_map: None,
+            error: None,
+        }
+    }
+
+    pub fn with_source_map(self, source_map: SourceMap) -> Self {
+        Tracer {
+            source_map: Some(source_map),
+            ..self
+        }
+    }
+
+    // the first write error is kept for finish() and stops the trace
+    pub fn record(&mut self, entry: &TraceEntry) {
+        if self.error.is_some() {
+            return;
+        }
+        let line = self.format_entry(entry);
+        if let Err(e) = writeln!(self.out, "{}", line) {
+            self.error = Some(e);
+        }
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => self.out.flush(),
+        }
+    }
+
+    fn format_entry(&self, e: &TraceEntry) -> String {
+        let inst = match Instruction::decode(&make_bus16(e.instruction as i32)) {
+            Ok(inst) => inst.to_string(),
+            Err(_) => format!("{:016b}", e.instruction),
+        };
+        let source = self
+            .source_map
+            .as_ref()
+            .and_then(|m| m.get(e.pc as usize))
+            .map(|l| l.to_string());
+        match self.format {
+            TraceFormat::Text => {
+                let mut line = format!(
+                    "{:>6} {:>5}: {:<12} A={}->{} D={}->{}",
+                    e.cycle, e.pc, inst, e.a.0, e.a.1, e.d.0, e.d.1
+                );
+                if let Some((addr, value)) = e.write {
+                    line += &format!(" RAM[{}]={}", addr, value);
+                }
+                if let Some(source) = source {
+                    line += &format!("  ; {}", source);
+                }
+                line
+            }
+            TraceFormat::JsonLines => {
+                let write = match e.write {
+                    Some((addr, value)) => format!("{{\"address\":{},\"value\":{}}}", addr, value),
+                    None => String::from("null"),
+                };
+                let mut line = format!(
+                    "{{\"cycle\":{},\"pc\":{},\"instruction\":{},\"word\":{},\"a\":[{},{}],\"d\":[{},{}],\"write\":{}",
+                    e.cycle,
+                    e.pc,
+                    json_string(&inst),
+                    e.instruction,
+                    e.a.0,
+                    e.a.1,
+                    e.d.0,
+                    e.d.1,
+                    write
+                );
+                if let Some(source) = source {
+                    line += &format!(",\"source\":{}", json_string(&source));
+                }
+                line + "}"
+            }
+        }
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            c if (c as u32) < 0x20 => out += &format!("\\u{:04x}", c as u32),
+            c => out.push(c),
+        }
+    }
+    out + "\""
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use std::cell::RefCell;
+    use std::io;
+    use std::io::Write;
+    use std::rc::Rc;
+
+    // clones share one buffer, so a test can read what a Tracer it handed a clone wrote
+    #[derive(Clone, Default)]
+    pub struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Buffer {
+        pub fn text(&self) -> String {
+            String::from_utf8(self.0.borrow().clone()).unwrap()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::Buffer;
+    use super::*;
+    use crate::assembly::assemble_with_source_map;
+
+    const ENTRY: TraceEntry = TraceEntry {
+        cycle: 3,
+        pc: 2,
+        // M=D+A
+        instruction: 0b1110_0000_1000_1000,
+        a: (100, 100),
+        d: (-1, -1),
+        write: Some((100, 99)),
+    };
+
+    #[test]
+    fn text_works() {
+        let buf = Buffer::default();
+        let mut t = Tracer::of(Box::new(buf.clone()), TraceFormat::Text);
+        t.record(&ENTRY);
+        t.record(&TraceEntry {
+            instruction: 0b1110_0000_0100_0000,
+            write: None,
+            ..ENTRY
+        });
+        t.finish().unwrap();
+        assert_eq!(
+            buf.text(),
+            "     3     2: M=D+A        A=100->100 D=-1->-1 RAM[100]=99\n     \
+             3     2: 1110000001000000 A=100->100 D=-1->-1\n"
+        );
+    }
+
+    #[test]
+    fn json_lines_work() {
+        let (_, map) = assemble_with_source_map("@100\nD=-1\n(X)\nM=D+A\n").unwrap();
+        let buf = Buffer::default();
+        let mut t = Tracer::of(Box::new(buf.clone()), TraceFormat::JsonLines).with_source_map(map);
+        t.record(&ENTRY);
+        t.record(&TraceEntry {
+            pc: 7,
+            write: None,
+            ..ENTRY
+        });
+        t.finish().unwrap();
+        assert_eq!(
+            buf.text(),
+            "{\"cycle\":3,\"pc\":2,\"instruction\":\"M=D+A\",\"word\":57480,\"a\":[100,100],\
+             \"d\":[-1,-1],\"write\":{\"address\":100,\"value\":99},\
+             \"source\":\"line 4 (X): M=D+A\"}\n\
+             {\"cycle\":3,\"pc\":7,\"instruction\":\"M=D+A\",\"word\":57480,\"a\":[100,100],\
+             \"d\":[-1,-1],\"write\":null}\n"
+        );
+    }
+
+    #[test]
+    fn read_works() {
+        assert_eq!(ENTRY.read(), None);
+        // M=M+1
+        let e = TraceEntry {
+            instruction: 0b1111_1101_1100_1000,
+            ..ENTRY
+        };
+        assert_eq!(e.read(), Some(100));
+        let e = TraceEntry {
+            instruction: 100,
+            ..ENTRY
+        };
+        assert_eq!(e.read(), None);
+    }
+
+    #[test]
+    fn json_string_escapes() {
+        assert_eq!(json_string("a\"b\\c\n"), "\"a\\\"b\\\\c\\u000a\"");
+    }
+}
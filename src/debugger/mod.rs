@@ -0,0 +1,776 @@
+use crate::assembly::{SourceMap, PREDEFINED_SYMBOLS};
+use crate::computer::{Computer, HaltReason, MutComputer, TraceEntry};
+use crate::gates::bus16::make_bus16;
+use crate::instruction::Instruction;
+
+const HELP: &str = "commands:
+  step [n]                 execute n instructions (s)
+  continue [max-cycles]    run until a breakpoint, watchpoint or halt (c)
+  reverse-step [n]         undo n instructions (rs)
+  reverse-continue         go back to the previous breakpoint (rc)
+  until <location>         run to a location (u)
+  break <location>         set a breakpoint (b)
+  delete <location>        remove a breakpoint (d)
+  watch <address> [read|write|access]
+  watch <address> <==|!=|<|<=|>|>=> <value>
+  unwatch <n>              remove watchpoint n
+  print[/d|/x|/b] <what>   show A, D, PC, M, an address or a range from..to (p)
+  info                     show registers, breakpoints and watchpoints (i)
+  who <address>            show the instruction that last wrote an address
+  list [location]          show instructions around a location (l)
+  reset                    set PC to 0, keeping RAM
+locations are ROM addresses, labels, or :<line> for a source line";
+
+const DEFAULT_MAX_CYCLES: u64 = 1_000_000;
+// cycles that can be stepped back
+const HISTORY: usize = 100_000;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Compare {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Watch {
+    Read,
+    Write,
+    Access,
+    Value(Compare, i16),
+}
+
+struct Watchpoint {
+    id: usize,
+    address: u16,
+    watch: Watch,
+    // for value watchpoints, whether the condition held after the last cycle
+    held: bool,
+}
+
+#[derive(Clone, Copy)]
+enum Radix {
+    Decimal,
+    Hex,
+    Binary,
+}
+
+// works the same over the gate-level and the fast computer
+pub struct Debugger<C: Computer = MutComputer> {
+    computer: C,
+    source_map: Option<SourceMap>,
+    watchpoints: Vec<Watchpoint>,
+    next_watchpoint: usize,
+    last_command: String,
+}
+
+impl<C: Computer> Debugger<C> {
+    pub fn of(mut computer: C, source_map: Option<SourceMap>) -> Self {
+        computer.keep_history(HISTORY);
+        Debugger {
+            computer,
+            source_map,
+            watchpoints: Vec::new(),
+            next_watchpoint: 1,
+            last_command: String::new(),
+        }
+    }
+
+    pub fn computer(&mut self) -> &mut C {
+        &mut self.computer
+    }
+
+    // an empty line repeats the last command
+    pub fn execute(&mut self, line: &str) -> Result<String, String> {
+        let line = match line.trim() {
+            "" => self.last_command.clone(),
+            l => l.to_string(),
+        };
+        self.last_command = line.clone();
+        let (command, args) = match line.split_once(char::is_whitespace) {
+            Some((c, a)) => (c, a.trim()),
+            None => (line.as_str(), ""),
+        };
+        let (command, radix) = match command.split_once('/') {
+            Some((c, r)) => (c, Some(r)),
+            None => (command, None),
+        };
+        match command {
+            "" => Ok(String::new()),
+            "step" | "s" => {
+                let n = if args.is_empty() {
+                    1
+                } else {
+                    parse_count(args)?
+                };
+                Ok(self.run(n, true))
+            }
+            "continue" | "c" => {
+                let n = if args.is_empty() {
+                    DEFAULT_MAX_CYCLES
+                } else {
+                    parse_count(args)?
+                };
+                Ok(self.run(n, false))
+            }
+            "reverse-step" | "rs" => {
+                let n = if args.is_empty() {
+                    1
+                } else {
+                    parse_count(args)?
+                };
+                let stepped = (0..n).take_while(|_| self.computer.step_back()).count();
+                self.refresh_watchpoints();
+                Ok(match stepped as u64 == n {
+                    true => self.current(),
+                    false => format!("reached the start of the history\n{}", self.current()),
+                })
+            }
+            "reverse-continue" | "rc" => {
+                let (n, reason) = self.computer.reverse_continue(DEFAULT_MAX_CYCLES);
+                self.refresh_watchpoints();
+                Ok(match reason {
+                    HaltReason::Breakpoint => format!("breakpoint\n{}", self.current()),
+                    HaltReason::HistoryExhausted => {
+                        format!("reached the start of the history\n{}", self.current())
+                    }
+                    _ => format!("went back {} cycles\n{}", n, self.current()),
+                })
+            }
+            "who" => {
+                let address = parse_address(args)?;
+                let value = self.computer.peek_ram(address);
+                Ok(match self.computer.last_write(address) {
+                    Some(w) => format!(
+                        "RAM[{}] = {} was written at cycle {} (was {})\n   {}",
+                        address,
+                        value,
+                        w.cycle,
+                        w.before,
+                        self.describe(w.pc)
+                    ),
+                    None => format!(
+                        "RAM[{}] = {} was not written in the last {} cycles",
+                        address,
+                        value,
+                        self.computer.history_len()
+                    ),
+                })
+            }
+            "until" | "u" => {
+                let pc = self.location(args)?;
+                let temporary = !self.computer.breakpoints().any(|b| b == pc);
+                self.computer.add_breakpoint(pc);
+                let out = self.run(DEFAULT_MAX_CYCLES, false);
+                if temporary {
+                    self.computer.remove_breakpoint(pc);
+                }
+                Ok(out)
+            }
+            "break" | "b" => {
+                let pc = self.location(args)?;
+                self.computer.add_breakpoint(pc);
+                Ok(format!("breakpoint at {}", self.describe(pc)))
+            }
+            "delete" | "d" => {
+                let pc = self.location(args)?;
+                if !self.computer.breakpoints().any(|b| b == pc) {
+                    return Err(format!("no breakpoint at {}", pc));
+                }
+                self.computer.remove_breakpoint(pc);
+                Ok(format!("deleted breakpoint at {}", pc))
+            }
+            "watch" | "w" => self.watch(args),
+            "unwatch" => {
+                let id = parse_count(args)? as usize;
+                let before = self.watchpoints.len();
+                self.watchpoints.retain(|w| w.id != id);
+                if self.watchpoints.len() == before {
+                    return Err(format!("no watchpoint {}", id));
+                }
+                Ok(format!("deleted watchpoint {}", id))
+            }
+            "print" | "p" => {
+                let radix = match radix {
+                    None | Some("d") => Radix::Decimal,
+                    Some("x") => Radix::Hex,
+                    Some("b") => Radix::Binary,
+                    Some(r) => return Err(format!("unknown format `{}`", r)),
+                };
+                self.print(args, radix)
+            }
+            "info" | "i" => Ok(self.info()),
+            "list" | "l" => {
+                let pc = if args.is_empty() {
+                    self.computer.peek_pc()
+                } else {
+                    self.location(args)?
+                };
+                Ok(self.list(pc))
+            }
+            "reset" => {
+                self.computer.reset();
+                Ok(self.current())
+            }
+            "help" | "h" => Ok(String::from(HELP)),
+            c => Err(format!("unknown command `{}`, try `help`", c)),
+        }
+    }
+
+    fn run(&mut self, max_cycles: u64, stepping: bool) -> String {
+        for cycle in 0..max_cycles {
+            let pc = self.computer.peek_pc();
+            if cycle > 0 && self.computer.breakpoints().any(|b| b == pc) {
+                return format!("breakpoint\n{}", self.current());
+            }
+            if !stepping && self.computer.is_halted() {
+                return format!("halted after {} cycles\n{}", cycle, self.current());
+            }
+            let entry = self.computer.step();
+            if let Some(hit) = self.check_watchpoints(&entry) {
+                return format!("{}\n{}", hit, self.current());
+            }
+        }
+        match stepping {
+            true => self.current(),
+            false => format!("stopped after {} cycles\n{}", max_cycles, self.current()),
+        }
+    }
+
+    fn check_watchpoints(&mut self, entry: &TraceEntry) -> Option<String> {
+        let mut hits = Vec::new();
+        for i in 0..self.watchpoints.len() {
+            let (id, address, watch) = {
+                let w = &self.watchpoints[i];
+                (w.id, w.address, w.watch)
+            };
+            let read = entry.read() == Some(address);
+            let write = entry.write.filter(|&(a, _)| a == address);
+            let hit = match watch {
+                Watch::Read if read => Some(String::from("read")),
+                Watch::Write | Watch::Access if write.is_some() => {
+                    Some(format!("written {}", write.unwrap().1))
+                }
+                Watch::Access if read => Some(String::from("read")),
+                Watch::Value(cmp, value) => {
+                    let v = self.computer.peek_ram(address);
+                    let holds = compare(cmp, v, value);
+                    let was = self.watchpoints[i].held;
+                    self.watchpoints[i].held = holds;
+                    if holds && !was {
+                        Some(format!("is {}", v))
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            };
+            if let Some(hit) = hit {
+                hits.push(format!("watchpoint {}: RAM[{}] {}", id, address, hit));
+            }
+        }
+        if hits.is_empty() {
+            None
+        } else {
+            Some(hits.join("\n"))
+        }
+    }
+
+    // value watchpoints trigger on a change, which stepping back must not fake
+    fn refresh_watchpoints(&mut self) {
+        for i in 0..self.watchpoints.len() {
+            if let Watch::Value(cmp, value) = self.watchpoints[i].watch {
+                let v = self.computer.peek_ram(self.watchpoints[i].address);
+                self.watchpoints[i].held = compare(cmp, v, value);
+            }
+        }
+    }
+
+    fn watch(&mut self, args: &str) -> Result<String, String> {
+        let words: Vec<&str> = args.split_whitespace().collect();
+        let (address, rest) = match words.split_first() {
+            Some((a, rest)) => (parse_address(a)?, rest),
+            None => return Err(String::from("watch needs an address")),
+        };
+        let watch = match rest {
+            [] | ["write"] => Watch::Write,
+            ["read"] => Watch::Read,
+            ["access"] => Watch::Access,
+            [op, value] => {
+                let cmp = match *op {
+                    "==" => Compare::Eq,
+                    "!=" => Compare::Ne,
+                    "<" => Compare::Lt,
+                    "<=" => Compare::Le,
+                    ">" => Compare::Gt,
+                    ">=" => Compare::Ge,
+                    _ => return Err(format!("unknown comparison `{}`", op)),
+                };
+                Watch::Value(cmp, parse_value(value)?)
+            }
+            _ => return Err(format!("invalid watchpoint `{}`", args)),
+        };
+        let held = match watch {
+            Watch::Value(cmp, value) => compare(cmp, self.computer.peek_ram(address), value),
+            _ => false,
+        };
+        let id = self.next_watchpoint;
+        self.next_watchpoint += 1;
+        self.watchpoints.push(Watchpoint {
+            id,
+            address,
+            watch,
+            held,
+        });
+        Ok(format!(
+            "watchpoint {}: {}",
+            id,
+            describe_watch(address, watch)
+        ))
+    }
+
+    fn print(&mut self, what: &str, radix: Radix) -> Result<String, String> {
+        let what = what.trim();
+        let upper = what.to_ascii_uppercase();
+        let value = |v: i16| format_value(v, radix);
+        match upper.as_str() {
+            "A" => return Ok(format!("A = {}", value(self.computer.peek_a()))),
+            "D" => return Ok(format!("D = {}", value(self.computer.peek_d()))),
+            "PC" => return Ok(format!("PC = {}", value(self.computer.peek_pc() as i16))),
+            "M" => {
+                let a = self.computer.peek_a() as u16 & 0x7fff;
+                let m = self.computer.peek_ram(a);
+                return Ok(format!("RAM[{}] = {}", a, value(m)));
+            }
+            _ => (),
+        }
+        let (from, to) = match what.split_once("..") {
+            Some((from, to)) => (parse_address(from)?, parse_address(to)?),
+            None => {
+                let a = parse_address(what)?;
+                (a, a + 1)
+            }
+        };
+        if to <= from {
+            return Err(format!("empty range `{}`", what));
+        }
+        let lines: Vec<String> = (from..to)
+            .map(|a| format!("RAM[{}] = {}", a, value(self.computer.peek_ram(a))))
+            .collect();
+        Ok(lines.join("\n"))
+    }
+
+    fn info(&mut self) -> String {
+        let mut lines = vec![
+            format!(
+                "A = {}  D = {}  PC = {}  cycles = {}",
+                self.computer.peek_a(),
+                self.computer.peek_d(),
+                self.computer.peek_pc(),
+                self.computer.cycles()
+            ),
+            self.current(),
+        ];
+        let breakpoints: Vec<u16> = self.computer.breakpoints().collect();
+        for pc in breakpoints {
+            lines.push(format!("breakpoint at {}", self.describe(pc)));
+        }
+        for w in self.watchpoints.iter() {
+            lines.push(format!(
+                "watchpoint {}: {}",
+                w.id,
+                describe_watch(w.address, w.watch)
+            ));
+        }
+        lines.join("\n")
+    }
+
+    fn list(&mut self, center: u16) -> String {
+        let pc = self.computer.peek_pc();
+        let breakpoints: Vec<u16> = self.computer.breakpoints().collect();
+        let last = match &self.source_map {
+            Some(m) => m.len().saturating_sub(1) as u16,
+            None => 0x7fff,
+        };
+        let from = center.saturating_sub(5);
+        let to = center.saturating_add(5).min(last);
+        (from..=to)
+            .map(|a| {
+                let marker = match (a == pc, breakpoints.contains(&a)) {
+                    (true, _) => "=>",
+                    (false, true) => " *",
+                    (false, false) => "  ",
+                };
+                format!("{} {}", marker, self.describe(a))
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn current(&mut self) -> String {
+        let pc = self.computer.peek_pc();
+        format!("=> {}", self.describe(pc))
+    }
+
+    // "12: M=D+1" followed by the source location when there is one
+    fn describe(&mut self, pc: u16) -> String {
+        let word = self.computer.peek_rom(pc);
+        let inst = match Instruction::decode(&make_bus16(word as i32)) {
+            Ok(inst) => inst.to_string(),
+            Err(_) => format!("{:016b}", word),
+        };
+        let text = format!("{:>5}: {}", pc, inst);
+        match self.source_map.as_ref().and_then(|m| m.get(pc as usize)) {
+            Some(loc) => format!("{:<20} ; {}", text, loc),
+            None => text,
+        }
+    }
+
+    // a ROM address, a label or :<line>
+    fn location(&self, s: &str) -> Result<u16, String> {
+        if s.is_empty() {
+            return Err(String::from("a location is needed"));
+        }
+        if let Some(line) = s.strip_prefix(':') {
+            let line = line
+                .parse()
+                .map_err(|_| format!("invalid line number `{}`", line))?;
+            let map = self
+                .source_map
+                .as_ref()
+                .ok_or_else(|| String::from("line numbers need assembly source"))?;
+            return map
+                .line_address(line)
+                .map(|a| a as u16)
+                .ok_or_else(|| format!("no instruction at or after line {}", line));
+        }
+        if let Ok(a) = parse_number(s) {
+            return if a <= 0x7fff {
+                Ok(a as u16)
+            } else {
+                Err(format!("address `{}` is out of range", s))
+            };
+        }
+        self.source_map
+            .as_ref()
+            .and_then(|m| m.label_address(s))
+            .map(|a| a as u16)
+            .ok_or_else(|| format!("unknown location `{}`", s))
+    }
+}
+
+fn compare(cmp: Compare, x: i16, y: i16) -> bool {
+    match cmp {
+        Compare::Eq => x == y,
+        Compare::Ne => x != y,
+        Compare::Lt => x < y,
+        Compare::Le => x <= y,
+        Compare::Gt => x > y,
+        Compare::Ge => x >= y,
+    }
+}
+
+fn describe_watch(address: u16, watch: Watch) -> String {
+    match watch {
+        Watch::Read => format!("read RAM[{}]", address),
+        Watch::Write => format!("write RAM[{}]", address),
+        Watch::Access => format!("access RAM[{}]", address),
+        Watch::Value(cmp, value) => {
+            let op = match cmp {
+                Compare::Eq => "==",
+                Compare::Ne => "!=",
+                Compare::Lt => "<",
+                Compare::Le => "<=",
+                Compare::Gt => ">",
+                Compare::Ge => ">=",
+            };
+            format!("RAM[{}] {} {}", address, op, value)
+        }
+    }
+}
+
+fn format_value(v: i16, radix: Radix) -> String {
+    match radix {
+        Radix::Decimal => v.to_string(),
+        Radix::Hex => format!("0x{:04x}", v as u16),
+        Radix::Binary => format!("{:016b}", v as u16),
+    }
+}
+
+// decimal or 0x hex
+fn parse_number(s: &str) -> Result<u32, String> {
+    let r = match s.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    r.map_err(|_| format!("invalid number `{}`", s))
+}
+
+fn parse_count(s: &str) -> Result<u64, String> {
+    s.parse().map_err(|_| format!("invalid count `{}`", s))
+}
+
+// a RAM address: a number, RAM[n] or a predefined symbol such as SP or SCREEN
+fn parse_address(s: &str) -> Result<u16, String> {
+    let s = s.trim();
+    let inner = s
+        .strip_prefix("RAM[")
+        .and_then(|r| r.strip_suffix(']'))
+        .unwrap_or(s);
+    let a = match PREDEFINED_SYMBOLS.iter().find(|(name, _)| *name == inner) {
+        Some(&(_, a)) => a as u32,
+        None => parse_number(inner)?,
+    };
+    if a > 0x7fff {
+        return Err(format!("address `{}` is out of range", s));
+    }
+    Ok(a as u16)
+}
+
+fn parse_value(s: &str) -> Result<i16, String> {
+    if let Some(hex) = s.strip_prefix("0x") {
+        return u16::from_str_radix(hex, 16)
+            .map(|v| v as i16)
+            .map_err(|_| format!("invalid value `{}`", s));
+    }
+    s.parse().map_err(|_| format!("invalid value `{}`", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembly::assemble_with_source_map;
+
+    // sums 1..=3 into RAM[17]
+    const CODE: &str = "@3
+D=A
+@16
+M=D
+@17
+M=0
+(LOOP)
+@16
+D=M
+@17
+M=D+M
+@16
+MD=M-1
+@LOOP
+D;JGT
+(END)
+@END
+0;JMP
+";
+
+    fn debugger() -> Debugger {
+        let (bin, map) = assemble_with_source_map(CODE).unwrap();
+        Debugger::of(MutComputer::of(&bin).unwrap(), Some(map))
+    }
+
+    #[test]
+    fn step_works() {
+        let mut d = debugger();
+        assert_eq!(
+            d.execute("step").unwrap(),
+            "=>     1: D=A           ; line 2: D=A"
+        );
+        assert_eq!(
+            d.execute("").unwrap(),
+            "=>     2: @16           ; line 3: @16"
+        );
+        assert_eq!(
+            d.execute("s 4").unwrap(),
+            "=>     6: @16           ; line 8 (LOOP): @16"
+        );
+        assert_eq!(d.execute("p D").unwrap(), "D = 3");
+    }
+
+    #[test]
+    fn breakpoints_work() {
+        let mut d = debugger();
+        assert_eq!(
+            d.execute("break LOOP").unwrap(),
+            "breakpoint at     6: @16           ; line 8 (LOOP): @16"
+        );
+        assert!(d.execute("c").unwrap().starts_with("breakpoint\n=>     6:"));
+        assert!(d.execute("c").unwrap().starts_with("breakpoint\n=>     6:"));
+        assert_eq!(d.execute("p 17").unwrap(), "RAM[17] = 3");
+        d.execute("delete LOOP").unwrap();
+        assert!(d.execute("c").unwrap().starts_with("halted after"));
+        assert_eq!(d.execute("p 17").unwrap(), "RAM[17] = 6");
+        assert_eq!(
+            d.execute("delete LOOP"),
+            Err(String::from("no breakpoint at 6"))
+        );
+    }
+
+    #[test]
+    fn locations_work() {
+        let d = debugger();
+        assert_eq!(d.location("LOOP"), Ok(6));
+        assert_eq!(d.location("13"), Ok(13));
+        assert_eq!(d.location("0x0d"), Ok(13));
+        assert_eq!(d.location(":7"), Ok(6));
+        assert_eq!(d.location(":12"), Ok(10));
+        assert_eq!(
+            d.location("NOPE"),
+            Err(String::from("unknown location `NOPE`"))
+        );
+        assert_eq!(
+            d.location(":99"),
+            Err(String::from("no instruction at or after line 99"))
+        );
+    }
+
+    #[test]
+    fn until_works() {
+        let mut d = debugger();
+        assert!(d
+            .execute("until END")
+            .unwrap()
+            .starts_with("breakpoint\n=>    14:"));
+        assert_eq!(d.computer().breakpoints().count(), 0);
+        assert_eq!(d.execute("p RAM[17]").unwrap(), "RAM[17] = 6");
+    }
+
+    #[test]
+    fn watchpoints_work() {
+        let mut d = debugger();
+        assert_eq!(
+            d.execute("watch 17").unwrap(),
+            "watchpoint 1: write RAM[17]"
+        );
+        assert_eq!(
+            d.execute("c").unwrap(),
+            "watchpoint 1: RAM[17] written 0\n=>     6: @16           ; line 8 (LOOP): @16"
+        );
+        assert!(d
+            .execute("c")
+            .unwrap()
+            .starts_with("watchpoint 1: RAM[17] written 3\n"));
+        d.execute("unwatch 1").unwrap();
+
+        assert_eq!(
+            d.execute("watch 16 read").unwrap(),
+            "watchpoint 2: read RAM[16]"
+        );
+        assert!(d
+            .execute("c")
+            .unwrap()
+            .starts_with("watchpoint 2: RAM[16] read\n=>    12: @6 "));
+        d.execute("unwatch 2").unwrap();
+
+        assert_eq!(
+            d.execute("watch 16 == 1").unwrap(),
+            "watchpoint 3: RAM[16] == 1"
+        );
+        assert!(d
+            .execute("c")
+            .unwrap()
+            .starts_with("watchpoint 3: RAM[16] is 1\n"));
+        assert!(d.execute("c").unwrap().starts_with("halted"));
+        assert_eq!(d.execute("unwatch 9"), Err(String::from("no watchpoint 9")));
+    }
+
+    #[test]
+    fn print_works() {
+        let mut d = debugger();
+        d.execute("s 4").unwrap();
+        assert_eq!(d.execute("p/x A").unwrap(), "A = 0x0010");
+        assert_eq!(d.execute("p/b D").unwrap(), "D = 0000000000000011");
+        assert_eq!(d.execute("p M").unwrap(), "RAM[16] = 3");
+        assert_eq!(d.execute("p PC").unwrap(), "PC = 4");
+        assert_eq!(d.execute("p 15..17").unwrap(), "RAM[15] = 0\nRAM[16] = 3");
+        assert_eq!(d.execute("p/x R15").unwrap(), "RAM[15] = 0x0000");
+        assert_eq!(d.execute("p/q A"), Err(String::from("unknown format `q`")));
+        assert_eq!(
+            d.execute("p 40000"),
+            Err(String::from("address `40000` is out of range"))
+        );
+    }
+
+    #[test]
+    fn list_works() {
+        let mut d = debugger();
+        d.execute("b 2").unwrap();
+        assert_eq!(
+            d.execute("list").unwrap(),
+            [
+                "=>     0: @3            ; line 1: @3",
+                "       1: D=A           ; line 2: D=A",
+                " *     2: @16           ; line 3: @16",
+                "       3: M=D           ; line 4: M=D",
+                "       4: @17           ; line 5: @17",
+                "       5: M=0           ; line 6: M=0",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn unknown_commands_are_reported() {
+        let mut d = debugger();
+        assert_eq!(
+            d.execute("jump 3"),
+            Err(String::from("unknown command `jump`, try `help`"))
+        );
+    }
+
+    #[test]
+    fn reverse_execution_works() {
+        let mut d = debugger();
+        d.execute("b LOOP").unwrap();
+        d.execute("c").unwrap();
+        d.execute("c").unwrap();
+        d.execute("s 3").unwrap();
+        assert_eq!(d.execute("p 17").unwrap(), "RAM[17] = 3");
+        assert_eq!(
+            d.execute("rs").unwrap(),
+            "=>     8: @17           ; line 10 (LOOP+2): @17"
+        );
+        assert!(d
+            .execute("rc")
+            .unwrap()
+            .starts_with("breakpoint\n=>     6:"));
+        assert_eq!(d.execute("p 17").unwrap(), "RAM[17] = 3");
+        assert_eq!(d.computer().cycles(), 14);
+        assert!(d
+            .execute("rc")
+            .unwrap()
+            .starts_with("breakpoint\n=>     6:"));
+        assert_eq!(d.execute("p 17").unwrap(), "RAM[17] = 0");
+        assert_eq!(
+            d.execute("rs 100").unwrap(),
+            "reached the start of the history\n=>     0: @3            ; line 1: @3"
+        );
+    }
+
+    #[test]
+    fn who_works() {
+        let mut d = debugger();
+        d.execute("until END").unwrap();
+        assert_eq!(
+            d.execute("who 16").unwrap(),
+            "RAM[16] = 0 was written at cycle 27 (was 1)\n      11: MD=M-1        ; line 13 (LOOP+5): MD=M-1"
+        );
+        assert_eq!(
+            d.execute("who R0").unwrap(),
+            "RAM[0] = 0 was not written in the last 30 cycles"
+        );
+    }
+
+    #[test]
+    fn fast_computer_works() {
+        let (bin, map) = assemble_with_source_map(CODE).unwrap();
+        let mut d = Debugger::of(crate::computer::FastComputer::of(&bin).unwrap(), Some(map));
+        d.execute("b LOOP").unwrap();
+        d.execute("c").unwrap();
+        d.execute("c").unwrap();
+        assert_eq!(d.execute("p 17").unwrap(), "RAM[17] = 3");
+        assert!(d.execute("rc").unwrap().starts_with("breakpoint\n=>     6:"));
+        assert_eq!(d.execute("p 17").unwrap(), "RAM[17] = 0");
+    }
+}
@@ -101,7 +101,7 @@ mod tests {
     use super::*;
     use crate::assert_bit_equals;
     use crate::assert_bus16_equals;
-    use crate::gates::bus16::testing::*;
+    use crate::gates::bus16::make_bus16;
     use std::ops::Not;
 
     #[test]
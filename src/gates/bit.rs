@@ -43,6 +43,14 @@ pub fn dmux8way(x: Bit, sel: &Bus3) -> [Bit; 8] {
     [a, b, c, d, e, f, g, h]
 }
 
+pub fn make_bit(b: bool) -> Bit {
+    if b {
+        Bit::Positive
+    } else {
+        Bit::Negative
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -189,15 +197,3 @@ mod test {
         ]
     }
 }
-
-pub mod testing {
-    use super::*;
-
-    pub fn make_bit(b: bool) -> Bit {
-        if b {
-            Bit::Positive
-        } else {
-            Bit::Negative
-        }
-    }
-}
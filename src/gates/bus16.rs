@@ -118,6 +118,8 @@ pub fn mux4way16(a: &Bus16, b: &Bus16, c: &Bus16, d: &Bus16, sel: &Bus2) -> Bus1
     mux(&s, &t, sel[0])
 }
 
+// one argument per input, as Mux8Way16 has
+#[allow(clippy::too_many_arguments)]
 pub fn mux8way16(
     a: &Bus16,
     b: &Bus16,
@@ -178,16 +180,29 @@ pub fn into_bus15(x: &Bus16) -> [Bit; 15] {
     ]
 }
 
-pub fn into_bus13(x: &Bus16) -> [Bit; 13] {
-    [
-        x[0x3], x[0x4], x[0x5], x[0x6], x[0x7], x[0x8], x[0x9], x[0xa], x[0xb], x[0xc], x[0xd],
-        x[0xe], x[0xf],
-    ]
+pub fn make_bus16(i: i32) -> Bus16 {
+    let mut b16 = [Bit::Negative; 16];
+    for b in 0..16 {
+        b16[b] = bit::make_bit(i & (1 << (15 - b)) != 0);
+    }
+    b16
+}
+
+pub fn into_i32(b: &Bus16) -> i32 {
+    let mut a = 0;
+    for i in 0..16 {
+        match b[i] {
+            Bit::Positive => {
+                a += 1 << (15 - i);
+            }
+            Bit::Negative => (),
+        }
+    }
+    a
 }
 
 #[cfg(test)]
 mod tests {
-    use super::testing::make_bus16;
     use super::*;
     use crate::assert_bit_equals;
     use crate::assert_bus16_equals;
@@ -411,50 +426,17 @@ mod tests {
             assert_bit_equals!(or16way(&bits), Bit::Positive);
         }
     }
-}
-
-pub mod testing {
-    use super::*;
-    use crate::gates::bit::testing::make_bit;
-
-    pub fn make_bus16(i: i32) -> Bus16 {
-        let mut b16 = [Bit::Negative; 16];
-        for b in 0..16 {
-            b16[b] = make_bit(i & (1 << (15 - b)) != 0);
-        }
-        b16
-    }
 
-    pub fn into_i32(b: &Bus16) -> i32 {
-        let mut a = 0;
-        for i in 0..16 {
-            match b[i] {
-                Bit::Positive => {
-                    a += 1 << (15 - i);
-                }
-                Bit::Negative => (),
-            }
-        }
-        a
+    #[test]
+    fn make_bus16_works() {
+        let a = make_bus16(0);
+        assert_bus16_equals!(a, [Bit::Negative; 16], format!("{:?}", a));
     }
 
-    #[cfg(test)]
-    mod tests {
-        use super::*;
-        use crate::assert_bit_equals;
-        use crate::assert_bus16_equals;
-
-        #[test]
-        fn make_bus16_works() {
-            let a = make_bus16(0);
-            assert_bus16_equals!(a, [Bit::Negative; 16], format!("{:?}", a));
-        }
-
-        #[test]
-        fn into_i32_works() {
-            for i in 0..10 {
-                assert_eq!(into_i32(&make_bus16(i)), i);
-            }
+    #[test]
+    fn into_i32_works() {
+        for i in 0..10 {
+            assert_eq!(into_i32(&make_bus16(i)), i);
         }
     }
 }
@@ -4,7 +4,7 @@ pub type Bus3 = [Bit; 3];
 
 pub mod testing {
     use super::*;
-    use crate::gates::bit::testing::make_bit;
+    use crate::gates::bit::make_bit;
 
     pub fn make_bus3(i: i32) -> Bus3 {
         let mut b3 = [Bit::Negative; 3];
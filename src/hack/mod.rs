@@ -0,0 +1,97 @@
+use crate::gates::bus16::Bus16;
+use crate::primitive::Bit;
+
+// .hack: one instruction per line, written as 16 '0'/'1' characters (MSB first)
+pub fn read_hack(text: &str) -> Result<Vec<Bus16>, String> {
+    let mut bin = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let n = line.chars().count();
+        if n != 16 {
+            return Err(format!(
+                "line {}: expected 16 characters, found {}",
+                i + 1,
+                n
+            ));
+        }
+        let mut word = [Bit::Negative; 16];
+        for (j, c) in line.chars().enumerate() {
+            word[j] = match c {
+                '0' => Bit::Negative,
+                '1' => Bit::Positive,
+                _ => {
+                    return Err(format!(
+                        "line {}: unexpected character `{}` at column {}",
+                        i + 1,
+                        c,
+                        j + 1
+                    ))
+                }
+            };
+        }
+        bin.push(word);
+    }
+    Ok(bin)
+}
+
+pub fn write_hack(bin: &[Bus16]) -> String {
+    let mut text = String::with_capacity(bin.len() * 17);
+    for word in bin {
+        for b in word {
+            text.push(match b {
+                Bit::Positive => '1',
+                Bit::Negative => '0',
+            });
+        }
+        text.push('\n');
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_bit_equals;
+    use crate::assert_bus16_equals;
+    use crate::gates::bus16::{into_i32, make_bus16};
+
+    #[test]
+    fn read_hack_works() {
+        let bin = read_hack("0000000000000010\n1110110000010000\r\n").unwrap();
+        assert_eq!(bin.len(), 2);
+        assert_eq!(into_i32(&bin[0]), 2);
+        assert_eq!(into_i32(&bin[1]), 0b1110110000010000);
+    }
+
+    #[test]
+    fn read_hack_reports_bad_line() {
+        assert_eq!(
+            read_hack("0000000000000010\n111011000001000\n").unwrap_err(),
+            "line 2: expected 16 characters, found 15"
+        );
+        assert_eq!(
+            read_hack("0000000000000010\n\n").unwrap_err(),
+            "line 2: expected 16 characters, found 0"
+        );
+        assert_eq!(
+            read_hack("0000000000000010\n0000000000000010\n11101100x0010000\n").unwrap_err(),
+            "line 3: unexpected character `x` at column 9"
+        );
+    }
+
+    #[test]
+    fn write_hack_works() {
+        let bin = vec![make_bus16(2), make_bus16(-1)];
+        assert_eq!(write_hack(&bin), "0000000000000010\n1111111111111111\n");
+    }
+
+    #[test]
+    fn round_trip() {
+        let bin = vec![make_bus16(0), make_bus16(12345), make_bus16(-2)];
+        let read = read_hack(&write_hack(&bin)).unwrap();
+        assert_eq!(read.len(), bin.len());
+        for (r, b) in read.iter().zip(bin.iter()) {
+            assert_bus16_equals!(r, b);
+        }
+    }
+}
@@ -2,8 +2,38 @@ use crate::general::Zero;
 
 use super::sequential_circuit::SequentialCircuit;
 
+pub struct ArraySC2<A: SequentialCircuit>([A; 2]);
+
+impl<A: SequentialCircuit + Zero> Zero for ArraySC2<A> {
+    fn new() -> Self {
+        Self([A::new(), A::new()])
+    }
+}
+
+impl<A: SequentialCircuit> SequentialCircuit for ArraySC2<A> {
+    type Input = [A::Input; 2];
+    type Output = [A::Output; 2];
+
+    fn tick(&self, input: &Self::Input) -> (Self::Output, Self) {
+        let Self(a) = self;
+        let (o0, s0) = a[0].tick(&input[0]);
+        let (o1, s1) = a[1].tick(&input[1]);
+        ([o0, o1], Self([s0, s1]))
+    }
+}
+
 pub struct ArraySC3<A: SequentialCircuit>([A; 3]);
 
+impl<A: SequentialCircuit> ArraySC3<A> {
+    pub fn at(&self, i: usize) -> &A {
+        &self.0[i]
+    }
+
+    pub fn at_mut(&mut self, i: usize) -> &mut A {
+        &mut self.0[i]
+    }
+}
+
 impl<A: SequentialCircuit + Zero> Zero for ArraySC3<A> {
     fn new() -> Self {
         Self([A::new(), A::new(), A::new()])
@@ -23,8 +53,40 @@ impl<A: SequentialCircuit> SequentialCircuit for ArraySC3<A> {
     }
 }
 
+pub struct ArraySC4<A: SequentialCircuit>([A; 4]);
+
+impl<A: SequentialCircuit + Zero> Zero for ArraySC4<A> {
+    fn new() -> Self {
+        Self([A::new(), A::new(), A::new(), A::new()])
+    }
+}
+
+impl<A: SequentialCircuit> SequentialCircuit for ArraySC4<A> {
+    type Input = [A::Input; 4];
+    type Output = [A::Output; 4];
+
+    fn tick(&self, input: &Self::Input) -> (Self::Output, Self) {
+        let Self(a) = self;
+        let (o0, s0) = a[0].tick(&input[0]);
+        let (o1, s1) = a[1].tick(&input[1]);
+        let (o2, s2) = a[2].tick(&input[2]);
+        let (o3, s3) = a[3].tick(&input[3]);
+        ([o0, o1, o2, o3], Self([s0, s1, s2, s3]))
+    }
+}
+
 pub struct ArraySC8<A: SequentialCircuit>([A; 8]);
 
+impl<A: SequentialCircuit> ArraySC8<A> {
+    pub fn at(&self, i: usize) -> &A {
+        &self.0[i]
+    }
+
+    pub fn at_mut(&mut self, i: usize) -> &mut A {
+        &mut self.0[i]
+    }
+}
+
 impl<A: SequentialCircuit + Zero> Zero for ArraySC8<A> {
     fn new() -> Self {
         Self([
@@ -63,6 +125,16 @@ impl<A: SequentialCircuit> SequentialCircuit for ArraySC8<A> {
 
 pub struct ArraySC16<A: SequentialCircuit>([A; 16]);
 
+impl<A: SequentialCircuit> ArraySC16<A> {
+    pub fn at(&self, i: usize) -> &A {
+        &self.0[i]
+    }
+
+    pub fn at_mut(&mut self, i: usize) -> &mut A {
+        &mut self.0[i]
+    }
+}
+
 impl<A: SequentialCircuit + Zero> Zero for ArraySC16<A> {
     fn new() -> Self {
         Self([
@@ -118,13 +190,3 @@ impl<A: SequentialCircuit> SequentialCircuit for ArraySC16<A> {
         )
     }
 }
-
-pub mod testing {
-    use super::*;
-
-    impl<A: SequentialCircuit> ArraySC8<A> {
-        pub fn at(&self, i: usize) -> &A {
-            &self.0[i]
-        }
-    }
-}
@@ -18,6 +18,16 @@ impl<S: SequentialCircuit + Zero, T: FeedbackSCDef<S>> Zero for FeedbackSC<S, T>
     }
 }
 
+impl<S: SequentialCircuit, T: FeedbackSCDef<S>> FeedbackSC<S, T> {
+    pub fn peek(&self) -> &S {
+        &self.sc
+    }
+
+    pub fn peek_mut(&mut self) -> &mut S {
+        &mut self.sc
+    }
+}
+
 pub trait FeedbackSCDef<S: SequentialCircuit> {
     type Input;
     type Output;
@@ -18,6 +18,16 @@ impl<S: SequentialCircuit, T: FeedforwardSCDef<S>> Zero for FeedforwardSC<S, T>
     }
 }
 
+impl<S: SequentialCircuit, T: FeedforwardSCDef<S>> FeedforwardSC<S, T> {
+    pub fn peek(&self) -> &S {
+        &self.sc
+    }
+
+    pub fn peek_mut(&mut self) -> &mut S {
+        &mut self.sc
+    }
+}
+
 pub trait FeedforwardSCDef<S: SequentialCircuit> {
     type Input;
     type Output;
@@ -39,13 +49,3 @@ impl<S: SequentialCircuit, T: FeedforwardSCDef<S>> SequentialCircuit for Feedfor
         (out, Self { sc, p: PhantomData })
     }
 }
-
-pub mod testing {
-    use super::*;
-
-    impl<S: SequentialCircuit, T: FeedforwardSCDef<S>> FeedforwardSC<S, T> {
-        pub fn peek(&self) -> &S {
-            &self.sc
-        }
-    }
-}
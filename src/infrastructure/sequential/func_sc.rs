@@ -20,6 +20,14 @@ impl<'a, S, I, O, F: Fn(&S, &I) -> (O, S)> FuncSC<'a, S, I, O, F> {
         }
     }
 
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+
+    pub fn state_mut(&mut self) -> &mut S {
+        &mut self.state
+    }
+
     fn transition(&self, input: &I) -> (O, S) {
         let f = self.f;
         f(&self.state, input)
@@ -7,6 +7,9 @@ pub use self::func_sc::*;
 mod array_sc;
 pub use self::array_sc::*;
 
+mod tuple_sc;
+pub use self::tuple_sc::*;
+
 pub mod primitive;
 
 mod feedback_sc;
@@ -17,8 +20,3 @@ pub use self::feedforward_sc::*;
 
 mod mut_sc;
 pub use self::mut_sc::*;
-
-pub mod testing {
-    pub use super::array_sc::testing::*;
-    pub use super::feedforward_sc::testing::*;
-}
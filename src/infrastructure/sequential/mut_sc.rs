@@ -57,6 +57,23 @@ impl<A: MutSC + Zero, B: MutSC + Zero> Zero for TupleMSC<A, B> {
     }
 }
 
+pub struct ArrayMSC2<A: MutSC>([A; 2]);
+
+impl<A: MutSC> MutSC for ArrayMSC2<A> {
+    type Input = [A::Input; 2];
+    type Output = [A::Output; 2];
+
+    fn tick(&mut self, input: &Self::Input) -> Self::Output {
+        [self.0[0].tick(&input[0]), self.0[1].tick(&input[1])]
+    }
+}
+
+impl<A: MutSC + Zero> Zero for ArrayMSC2<A> {
+    fn new() -> Self {
+        Self([A::new(), A::new()])
+    }
+}
+
 pub struct FeedforwardMSC<A: MutSC, D: FeedforwardMSCDef<A>> {
     msc: A,
     p: PhantomData<D>,
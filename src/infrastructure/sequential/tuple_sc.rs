@@ -0,0 +1,23 @@
+use crate::general::Zero;
+
+use super::sequential_circuit::SequentialCircuit;
+
+// two different circuits side by side
+pub struct TupleSC<A: SequentialCircuit, B: SequentialCircuit>(A, B);
+
+impl<A: SequentialCircuit + Zero, B: SequentialCircuit + Zero> Zero for TupleSC<A, B> {
+    fn new() -> Self {
+        Self(A::new(), B::new())
+    }
+}
+
+impl<A: SequentialCircuit, B: SequentialCircuit> SequentialCircuit for TupleSC<A, B> {
+    type Input = (A::Input, B::Input);
+    type Output = (A::Output, B::Output);
+
+    fn tick(&self, input: &Self::Input) -> (Self::Output, Self) {
+        let (o0, s0) = self.0.tick(&input.0);
+        let (o1, s1) = self.1.tick(&input.1);
+        ((o0, o1), Self(s0, s1))
+    }
+}
@@ -1,14 +1,42 @@
+use std::fmt;
+
 use crate::gates::bus16::Bus16;
 use crate::primitive::Bit;
 
-use crate::gates::bus16::testing::make_bus16;
+use crate::gates::bus16::{into_i32, make_bus16};
 
-#[derive(Debug)]
+#[derive(Debug, Eq, PartialEq)]
 pub enum Instruction {
-    A(i32),
+    A(Address),
     C(Computation),
 }
 
+// the 15-bit value loaded by an A-instruction; bit 15 is left for the opcode
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Address(u16);
+
+impl Address {
+    pub const MAX: i32 = 0x7fff;
+
+    pub fn of(value: i32) -> Option<Self> {
+        if (0..=Self::MAX).contains(&value) {
+            Some(Address(value as u16))
+        } else {
+            None
+        }
+    }
+
+    pub fn value(&self) -> i32 {
+        self.0 as i32
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 #[derive(Debug, Eq, PartialEq)]
 pub struct Computation {
     pub comp: (CompReg, Comp),
@@ -16,13 +44,13 @@ pub struct Computation {
     pub jump: Jump,
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum CompReg {
     A,
     M,
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Comp {
     Zero,
     One,
@@ -44,7 +72,7 @@ pub enum Comp {
     DOrA,
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Jump {
     None,
     Eq,
@@ -57,7 +85,7 @@ pub enum Jump {
     Always,
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Dest {
     A,
     AD,
@@ -69,12 +97,30 @@ pub enum Dest {
     None,
 }
 
+#[derive(Debug, Eq, PartialEq)]
+pub enum DecodeError {
+    // a-bit and ALU control bits that are not in the comp table
+    UndefinedComp(u16),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UndefinedComp(c) => write!(f, "undefined comp bits {:07b}", c),
+        }
+    }
+}
+
 impl Instruction {
+    pub fn a(value: i32) -> Option<Self> {
+        Address::of(value).map(Instruction::A)
+    }
+
     pub fn bus16(&self) -> Bus16 {
         let (p, n) = (Bit::Positive, Bit::Negative);
         let b = |bl: bool| if bl { p } else { n };
         match self {
-            &Instruction::A(i) => make_bus16(i),
+            Instruction::A(i) => make_bus16(i.value()),
             Instruction::C(c) => {
                 let a = match c.comp.0 {
                     CompReg::A => Bit::Negative,
@@ -93,7 +139,7 @@ impl Instruction {
                     Comp::DPlus1 => [n, p, p, p, p, p],
                     Comp::APlus1 => [p, p, n, p, p, p],
                     Comp::DMinus1 => [n, n, p, p, p, n],
-                    Comp::AMinus1 => [p, p, n, p, p, p],
+                    Comp::AMinus1 => [p, p, n, n, p, n],
                     Comp::DPlusA => [n, n, n, n, p, n],
                     Comp::DMinusA => [n, p, n, n, p, p],
                     Comp::AMinusD => [n, n, n, p, p, p],
@@ -140,3 +186,309 @@ impl Instruction {
         }
     }
 }
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::A(i) => write!(f, "@{}", i),
+            Instruction::C(c) => write!(f, "{}", c),
+        }
+    }
+}
+
+impl fmt::Display for Computation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let dest = match self.dest {
+            Dest::None => "",
+            Dest::M => "M=",
+            Dest::D => "D=",
+            Dest::DM => "MD=",
+            Dest::A => "A=",
+            Dest::AM => "AM=",
+            Dest::AD => "AD=",
+            Dest::ADM => "AMD=",
+        };
+        let comp = match self.comp.1 {
+            Comp::Zero => "0",
+            Comp::One => "1",
+            Comp::MinusOne => "-1",
+            Comp::D => "D",
+            Comp::A => "A",
+            Comp::NotD => "!D",
+            Comp::NotA => "!A",
+            Comp::MinusD => "-D",
+            Comp::MinusA => "-A",
+            Comp::DPlus1 => "D+1",
+            Comp::APlus1 => "A+1",
+            Comp::DMinus1 => "D-1",
+            Comp::AMinus1 => "A-1",
+            Comp::DPlusA => "D+A",
+            Comp::DMinusA => "D-A",
+            Comp::AMinusD => "A-D",
+            Comp::DAndA => "D&A",
+            Comp::DOrA => "D|A",
+        };
+        let jump = match self.jump {
+            Jump::None => "",
+            Jump::Gt => ";JGT",
+            Jump::Eq => ";JEQ",
+            Jump::Ge => ";JGE",
+            Jump::Lt => ";JLT",
+            Jump::Ne | Jump::NEq => ";JNE",
+            Jump::Le => ";JLE",
+            Jump::Always => ";JMP",
+        };
+        match self.comp.0 {
+            CompReg::A => write!(f, "{}{}{}", dest, comp, jump),
+            CompReg::M => write!(f, "{}{}{}", dest, comp.replace('A', "M"), jump),
+        }
+    }
+}
+
+impl Instruction {
+    // the CPU ignores bits 13 and 14 of a C-instruction, and so does decoding.
+    // bus16 sets them, so such words do not survive a round trip bit for bit.
+    pub fn decode(word: &Bus16) -> Result<Self, DecodeError> {
+        let w = into_i32(word) as u16;
+        if w & 0x8000 == 0 {
+            return Ok(Instruction::A(Address(w)));
+        }
+        let comp_bits = (w >> 6) & 0x7f;
+        let comp = decode_comp(comp_bits).ok_or(DecodeError::UndefinedComp(comp_bits))?;
+        let dest = match (w >> 3) & 0x7 {
+            0b000 => Dest::None,
+            0b001 => Dest::M,
+            0b010 => Dest::D,
+            0b011 => Dest::DM,
+            0b100 => Dest::A,
+            0b101 => Dest::AM,
+            0b110 => Dest::AD,
+            _ => Dest::ADM,
+        };
+        let jump = match w & 0x7 {
+            0b000 => Jump::None,
+            0b001 => Jump::Gt,
+            0b010 => Jump::Eq,
+            0b011 => Jump::Ge,
+            0b100 => Jump::Lt,
+            0b101 => Jump::Ne,
+            0b110 => Jump::Le,
+            _ => Jump::Always,
+        };
+        Ok(Instruction::C(Computation { comp, dest, jump }))
+    }
+}
+
+fn decode_comp(bits: u16) -> Option<(CompReg, Comp)> {
+    let comp = match bits & 0x3f {
+        0b101010 => Comp::Zero,
+        0b111111 => Comp::One,
+        0b111010 => Comp::MinusOne,
+        0b001100 => Comp::D,
+        0b110000 => Comp::A,
+        0b001101 => Comp::NotD,
+        0b110001 => Comp::NotA,
+        0b001111 => Comp::MinusD,
+        0b110011 => Comp::MinusA,
+        0b011111 => Comp::DPlus1,
+        0b110111 => Comp::APlus1,
+        0b001110 => Comp::DMinus1,
+        0b110010 => Comp::AMinus1,
+        0b000010 => Comp::DPlusA,
+        0b010011 => Comp::DMinusA,
+        0b000111 => Comp::AMinusD,
+        0b000000 => Comp::DAndA,
+        0b010101 => Comp::DOrA,
+        _ => return None,
+    };
+    if bits & 0x40 == 0 {
+        return Some((CompReg::A, comp));
+    }
+    match comp {
+        Comp::A
+        | Comp::NotA
+        | Comp::MinusA
+        | Comp::APlus1
+        | Comp::AMinus1
+        | Comp::DPlusA
+        | Comp::DMinusA
+        | Comp::AMinusD
+        | Comp::DAndA
+        | Comp::DOrA => Some((CompReg::M, comp)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_bit_equals;
+    use crate::assert_bus16_equals;
+
+    const COMPS: [(CompReg, Comp); 28] = [
+        (CompReg::A, Comp::Zero),
+        (CompReg::A, Comp::One),
+        (CompReg::A, Comp::MinusOne),
+        (CompReg::A, Comp::D),
+        (CompReg::A, Comp::A),
+        (CompReg::A, Comp::NotD),
+        (CompReg::A, Comp::NotA),
+        (CompReg::A, Comp::MinusD),
+        (CompReg::A, Comp::MinusA),
+        (CompReg::A, Comp::DPlus1),
+        (CompReg::A, Comp::APlus1),
+        (CompReg::A, Comp::DMinus1),
+        (CompReg::A, Comp::AMinus1),
+        (CompReg::A, Comp::DPlusA),
+        (CompReg::A, Comp::DMinusA),
+        (CompReg::A, Comp::AMinusD),
+        (CompReg::A, Comp::DAndA),
+        (CompReg::A, Comp::DOrA),
+        (CompReg::M, Comp::A),
+        (CompReg::M, Comp::NotA),
+        (CompReg::M, Comp::MinusA),
+        (CompReg::M, Comp::APlus1),
+        (CompReg::M, Comp::AMinus1),
+        (CompReg::M, Comp::DPlusA),
+        (CompReg::M, Comp::DMinusA),
+        (CompReg::M, Comp::AMinusD),
+        (CompReg::M, Comp::DAndA),
+        (CompReg::M, Comp::DOrA),
+    ];
+
+    const DESTS: [Dest; 8] = [
+        Dest::None,
+        Dest::M,
+        Dest::D,
+        Dest::DM,
+        Dest::A,
+        Dest::AM,
+        Dest::AD,
+        Dest::ADM,
+    ];
+
+    const JUMPS: [Jump; 8] = [
+        Jump::None,
+        Jump::Gt,
+        Jump::Eq,
+        Jump::Ge,
+        Jump::Lt,
+        Jump::Ne,
+        Jump::Le,
+        Jump::Always,
+    ];
+
+    #[test]
+    fn decode_a_instruction() {
+        for i in [0, 1, 100, 0x4000, 0x7fff] {
+            assert_eq!(
+                Instruction::decode(&Instruction::a(i).unwrap().bus16()),
+                Ok(Instruction::a(i).unwrap())
+            );
+        }
+    }
+
+    #[test]
+    fn a_instruction_is_15_bit() {
+        assert_eq!(
+            Instruction::a(0x7fff),
+            Some(Instruction::A(Address(0x7fff)))
+        );
+        assert_eq!(Instruction::a(0x8000), None);
+        assert_eq!(Instruction::a(70000), None);
+        assert_eq!(Instruction::a(-1), None);
+    }
+
+    #[test]
+    fn decode_inverts_bus16() {
+        for (reg, comp) in COMPS {
+            for dest in DESTS {
+                for jump in JUMPS {
+                    let inst = Instruction::C(Computation {
+                        comp: (reg, comp),
+                        dest,
+                        jump,
+                    });
+                    assert_eq!(Instruction::decode(&inst.bus16()), Ok(inst));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bus16_inverts_decode() {
+        let mut defined = 0;
+        for w in 0..=0xffff {
+            let word = make_bus16(w);
+            if let Ok(inst) = Instruction::decode(&word) {
+                let canonical = if w & 0x8000 == 0 { w } else { w | 0x6000 };
+                assert_bus16_equals!(inst.bus16(), make_bus16(canonical), format!("{:016b}", w));
+                defined += 1;
+            }
+        }
+        assert_eq!(defined, 0x8000 + 4 * 28 * 8 * 8);
+    }
+
+    #[test]
+    fn decode_rejects_undefined_patterns() {
+        assert_eq!(
+            Instruction::decode(&make_bus16(0b1110_0000_0100_0000)),
+            Err(DecodeError::UndefinedComp(0b0000001))
+        );
+        // "0" has no M form
+        assert_eq!(
+            Instruction::decode(&make_bus16(0b1111_1010_1000_0000)),
+            Err(DecodeError::UndefinedComp(0b1101010))
+        );
+    }
+
+    #[test]
+    fn decode_ignores_bits_13_and_14() {
+        for w in [
+            0b1000_1010_1000_0111,
+            0b1010_1010_1000_0111,
+            0b1100_1010_1000_0111,
+        ] {
+            assert_eq!(
+                Instruction::decode(&make_bus16(w)),
+                Instruction::decode(&make_bus16(0b1110_1010_1000_0111))
+            );
+        }
+    }
+
+    #[test]
+    fn display_works() {
+        assert_eq!(Instruction::a(12).unwrap().to_string(), "@12");
+        assert_eq!(
+            Instruction::C(Computation {
+                comp: (CompReg::M, Comp::DMinusA),
+                dest: Dest::DM,
+                jump: Jump::None,
+            })
+            .to_string(),
+            "MD=D-M"
+        );
+        assert_eq!(
+            Instruction::C(Computation {
+                comp: (CompReg::A, Comp::Zero),
+                dest: Dest::None,
+                jump: Jump::Always,
+            })
+            .to_string(),
+            "0;JMP"
+        );
+    }
+
+    #[test]
+    fn minus_1_encodes_as_110010() {
+        for reg in [CompReg::A, CompReg::M] {
+            let inst = Instruction::C(Computation {
+                comp: (reg, Comp::AMinus1),
+                dest: Dest::None,
+                jump: Jump::None,
+            });
+            let w = into_i32(&inst.bus16());
+            assert_eq!((w >> 6) & 0x3f, 0b110010, "{}", inst);
+        }
+    }
+}
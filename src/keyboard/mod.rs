@@ -0,0 +1,247 @@
+use std::collections::BTreeMap;
+
+use crate::general::Zero;
+use crate::infrastructure::sequential::MutSC;
+
+// key codes of the Hack character set beyond printable ASCII; 0 means no key
+pub const NEWLINE: u16 = 128;
+pub const BACKSPACE: u16 = 129;
+pub const LEFT: u16 = 130;
+pub const UP: u16 = 131;
+pub const RIGHT: u16 = 132;
+pub const DOWN: u16 = 133;
+pub const HOME: u16 = 134;
+pub const END: u16 = 135;
+pub const PAGE_UP: u16 = 136;
+pub const PAGE_DOWN: u16 = 137;
+pub const INSERT: u16 = 138;
+pub const DELETE: u16 = 139;
+pub const ESC: u16 = 140;
+pub const F1: u16 = 141;
+pub const F12: u16 = 152;
+
+const NAMES: [(&str, u16); 14] = [
+    ("SPACE", b' ' as u16),
+    ("NEWLINE", NEWLINE),
+    ("BACKSPACE", BACKSPACE),
+    ("LEFT", LEFT),
+    ("UP", UP),
+    ("RIGHT", RIGHT),
+    ("DOWN", DOWN),
+    ("HOME", HOME),
+    ("END", END),
+    ("PAGEUP", PAGE_UP),
+    ("PAGEDOWN", PAGE_DOWN),
+    ("INSERT", INSERT),
+    ("DELETE", DELETE),
+    ("ESC", ESC),
+];
+
+// a single printable character, a name such as NEWLINE or F5, or a decimal key code
+pub fn key_code(name: &str) -> Option<u16> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return if (' '..='~').contains(&c) {
+            Some(c as u16)
+        } else {
+            None
+        };
+    }
+    let upper = name.to_ascii_uppercase();
+    if let Some(&(_, code)) = NAMES.iter().find(|(n, _)| *n == upper) {
+        return Some(code);
+    }
+    if let Some(n) = upper.strip_prefix('F').and_then(|n| n.parse::<u16>().ok()) {
+        return if (1..=12).contains(&n) {
+            Some(F1 + n - 1)
+        } else {
+            None
+        };
+    }
+    match name.parse() {
+        Ok(code) if code <= F12 => Some(code),
+        _ => None,
+    }
+}
+
+// the key held down during each cycle; scheduled events take effect on their cycle
+pub struct Keyboard {
+    cycle: u64,
+    key: u16,
+    events: BTreeMap<u64, u16>,
+}
+
+impl Zero for Keyboard {
+    fn new() -> Self {
+        Keyboard {
+            cycle: 0,
+            key: 0,
+            events: BTreeMap::new(),
+        }
+    }
+}
+
+impl MutSC for Keyboard {
+    type Input = ();
+    type Output = u16;
+
+    fn tick(&mut self, _: &Self::Input) -> Self::Output {
+        if let Some(key) = self.events.remove(&self.cycle) {
+            self.key = key;
+        }
+        self.cycle += 1;
+        self.key
+    }
+}
+
+impl Keyboard {
+    // one event per line: `<cycle> <key>` or `<cycle> release`; lines starting with `#` are comments
+    pub fn of_script(script: &str) -> Result<Self, String> {
+        let mut keyboard = Keyboard::new();
+        // cycle -> line it was scheduled on
+        let mut lines = BTreeMap::new();
+        for (i, line) in script.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = |msg: String| format!("line {}: {}", i + 1, msg);
+            let (cycle, key) = line
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| err(String::from("expected `<cycle> <key>`")))?;
+            let cycle = cycle
+                .parse()
+                .map_err(|_| err(format!("invalid cycle `{}`", cycle)))?;
+            let key = match key.trim() {
+                "release" => 0,
+                k => key_code(k).ok_or_else(|| err(format!("unknown key `{}`", k)))?,
+            };
+            if let Some(first) = lines.insert(cycle, i + 1) {
+                return Err(err(format!(
+                    "cycle {} already has an event on line {}",
+                    cycle, first
+                )));
+            }
+            keyboard.schedule(cycle, key);
+        }
+        Ok(keyboard)
+    }
+
+    // one event per cycle: this replaces any event already scheduled for the cycle
+    pub fn schedule(&mut self, cycle: u64, key: u16) {
+        self.events.insert(cycle, key);
+    }
+
+    pub fn press(&mut self, key: u16) {
+        self.key = key;
+    }
+
+    pub fn key(&self) -> u16 {
+        self.key
+    }
+
+    pub fn cycle(&self) -> u64 {
+        self.cycle
+    }
+
+    // events that have not happened yet
+    pub fn scheduled(&self) -> impl Iterator<Item = (u64, u16)> + '_ {
+        self.events.iter().map(|(&cycle, &key)| (cycle, key))
+    }
+
+    // the event the next tick takes effect
+    pub(crate) fn pending(&self) -> Option<u16> {
+        self.events.get(&self.cycle).copied()
+    }
+
+    // undoes a tick, given what pending() and key() told before it
+    pub(crate) fn rewind(&mut self, key: u16, event: Option<u16>) {
+        self.cycle -= 1;
+        self.key = key;
+        if let Some(e) = event {
+            self.events.insert(self.cycle, e);
+        }
+    }
+
+    pub(crate) fn of_state(cycle: u64, key: u16, events: BTreeMap<u64, u16>) -> Self {
+        Keyboard { cycle, key, events }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_code_works() {
+        assert_eq!(key_code("a"), Some(97));
+        assert_eq!(key_code("#"), Some(35));
+        assert_eq!(key_code("5"), Some(53));
+        assert_eq!(key_code("space"), Some(32));
+        assert_eq!(key_code("NEWLINE"), Some(128));
+        assert_eq!(key_code("Left"), Some(130));
+        assert_eq!(key_code("F1"), Some(141));
+        assert_eq!(key_code("f12"), Some(152));
+        assert_eq!(key_code("75"), Some(75));
+        assert_eq!(key_code("F13"), None);
+        assert_eq!(key_code("153"), None);
+        assert_eq!(key_code("LEFTT"), None);
+        assert_eq!(key_code("\t"), None);
+    }
+
+    #[test]
+    fn keyboard_works() {
+        let mut k = Keyboard::new();
+        k.schedule(2, LEFT);
+        k.schedule(4, 0);
+        k.schedule(5, b'x' as u16);
+        let keys: Vec<u16> = (0..7).map(|_| k.tick(&())).collect();
+        assert_eq!(keys, vec![0, 0, LEFT, LEFT, 0, 120, 120]);
+        assert_eq!(k.cycle(), 7);
+        k.press(UP);
+        assert_eq!(k.tick(&()), UP);
+    }
+
+    #[test]
+    fn rewind_works() {
+        let mut k = Keyboard::new();
+        k.schedule(1, LEFT);
+        k.tick(&());
+        let (key, event) = (k.key(), k.pending());
+        assert_eq!(event, Some(LEFT));
+        assert_eq!(k.tick(&()), LEFT);
+        k.rewind(key, event);
+        assert_eq!(k.cycle(), 1);
+        assert_eq!(k.key(), 0);
+        assert_eq!(k.scheduled().collect::<Vec<_>>(), vec![(1, LEFT)]);
+        assert_eq!(k.tick(&()), LEFT);
+    }
+
+    #[test]
+    fn of_script_works() {
+        let script = "# cycle key\n1 A\n3 release\n\n4 NEWLINE\n5 #\n";
+        let mut k = Keyboard::of_script(script).unwrap();
+        let keys: Vec<u16> = (0..6).map(|_| k.tick(&())).collect();
+        assert_eq!(keys, vec![0, 65, 65, 0, 128, 35]);
+    }
+
+    #[test]
+    fn of_script_reports_errors() {
+        let e = |s| Keyboard::of_script(s).err();
+        assert_eq!(e("10\n"), Some(String::from("line 1: expected `<cycle> <key>`")));
+        assert_eq!(e("\nx A\n"), Some(String::from("line 2: invalid cycle `x`")));
+        assert_eq!(e("1 FOO\n"), Some(String::from("line 1: unknown key `FOO`")));
+        assert_eq!(
+            e("3 A\n4 B\n# again\n3 C\n"),
+            Some(String::from("line 4: cycle 3 already has an event on line 1"))
+        );
+    }
+
+    #[test]
+    fn schedule_replaces_the_event_of_the_same_cycle() {
+        let mut k = Keyboard::new();
+        k.schedule(1, 65);
+        k.schedule(1, 66);
+        assert_eq!(k.scheduled().collect::<Vec<_>>(), vec![(1, 66)]);
+    }
+}
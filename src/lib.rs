@@ -0,0 +1,38 @@
+#![feature(map_try_insert)]
+
+mod assembly;
+mod computer;
+mod debugger;
+mod gates;
+mod general;
+mod hack;
+mod infrastructure;
+mod instruction;
+pub mod keyboard;
+mod parser;
+mod primitive;
+mod profiler;
+mod screen;
+mod sequential;
+mod terminal;
+
+pub use crate::assembly::{
+    assemble, assemble_with_source_map, disassemble, AsmError, AsmErrorKind, SourceLocation,
+    SourceMap,
+};
+pub use crate::computer::{
+    fuzz, lockstep, random_program, Computer, CpuState, Divergence, FastComputer, GateComputer,
+    HaltReason, LastWrite, MutComputer, Snapshot, TraceEntry, TraceFormat, Tracer, XorShift,
+};
+pub use crate::debugger::Debugger;
+pub use crate::gates::bus16::{into_i32, make_bus16, Bus16};
+pub use crate::hack::{read_hack, write_hack};
+pub use crate::infrastructure::sequential::{MutSC, SequentialCircuit};
+pub use crate::instruction::{
+    Address, Comp, CompReg, Computation, DecodeError, Dest, Instruction, Jump,
+};
+pub use crate::keyboard::Keyboard;
+pub use crate::primitive::Bit;
+pub use crate::profiler::{FunctionProfile, HotLoop, Profiler};
+pub use crate::screen::Screen;
+pub use crate::terminal::decode_keys;
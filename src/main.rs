@@ -1,18 +1,618 @@
-#![feature(array_zip, assert_matches, map_try_insert)]
-
-mod assembly;
-mod computer;
-mod gates;
-mod general;
-mod infrastructure;
-mod instruction;
-mod parser;
-mod primitive;
-mod sequential;
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::process::{self, Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rusty_nand2tetris::{
+    assemble_with_source_map, decode_keys, disassemble, fuzz, lockstep, read_hack, write_hack,
+    Bus16, Computer, Debugger, FastComputer, GateComputer, HaltReason, Instruction, Keyboard,
+    MutComputer, Profiler, Screen, Snapshot, SourceMap, TraceFormat, Tracer,
+};
+
+const USAGE: &str = "usage: rusty-nand2tetris <command> [options]
+
+commands:
+  assemble <input.asm> [-o <output.hack>]
+  run <input.asm|input.hack|input.snap> [--cycles <n>] [--ram <addr>|<from>..<to>]
+      [--screen <output.pbm|output.ppm> [--frame-every <n>]] [--keys <script>]
+      [--trace <output|-> [--trace-format text|json]] [--snapshot <output.snap>] [--fast|--gates]
+  play <input.asm|input.hack|input.snap> [--cycles-per-frame <n>] [--scale <n>] [--half-blocks] [--fast|--gates]
+  debug <input.asm|input.hack|input.snap> [--keys <script>] [--fast|--gates]
+  profile <input.asm|input.hack|input.snap> [--cycles <n>] [--keys <script>] [--top <n>]
+      [--folded <output.folded>] [--fast|--gates]
+  difftest <input.asm|input.hack> [--cycles <n>]
+  difftest --fuzz [--seed <n>] [--runs <n>] [--length <n>] [--cycles <n>]
+  disasm <input.hack> [-o <output.asm>]";
+
+enum CliError {
+    Usage(String),
+    Failure(String),
+}
 
 fn main() {
-    let a = primitive::Bit::Negative;
-    let b = primitive::Bit::Positive;
-    let _ = a.nand(b);
-    println!("Hello, world!");
+    let args: Vec<String> = env::args().skip(1).collect();
+    match run_command(&args) {
+        Ok(()) => (),
+        Err(CliError::Usage(msg)) => {
+            eprintln!("error: {}\n\n{}", msg, USAGE);
+            process::exit(2);
+        }
+        Err(CliError::Failure(msg)) => {
+            for line in msg.lines() {
+                eprintln!("error: {}", line);
+            }
+            process::exit(1);
+        }
+    }
+}
+
+fn run_command(args: &[String]) -> Result<(), CliError> {
+    let (command, rest) = args
+        .split_first()
+        .ok_or_else(|| CliError::Usage(String::from("no command given")))?;
+    // --fast swaps the gate-level computer for the behavioral one; --gates keeps ROM and
+    // data memory as immutable gate circuits too, which is thousands of times slower
+    let fast = rest.iter().any(|a| a == "--fast");
+    let gates = rest.iter().any(|a| a == "--gates");
+    if fast && gates {
+        return Err(CliError::Usage(String::from(
+            "--fast and --gates cannot be combined",
+        )));
+    }
+    let simulated: Vec<String> = rest
+        .iter()
+        .filter(|a| *a != "--fast" && *a != "--gates")
+        .cloned()
+        .collect();
+    match command.as_str() {
+        "assemble" => assemble_command(rest),
+        "run" if fast => run_program_command::<FastComputer>(&simulated),
+        "run" if gates => run_program_command::<GateComputer>(&simulated),
+        "run" => run_program_command::<MutComputer>(rest),
+        "play" if fast => play_command::<FastComputer>(&simulated),
+        "play" if gates => play_command::<GateComputer>(&simulated),
+        "play" => play_command::<MutComputer>(rest),
+        "debug" if fast => debug_command::<FastComputer>(&simulated),
+        "debug" if gates => debug_command::<GateComputer>(&simulated),
+        "debug" => debug_command::<MutComputer>(rest),
+        "profile" if fast => profile_command::<FastComputer>(&simulated),
+        "profile" if gates => profile_command::<GateComputer>(&simulated),
+        "profile" => profile_command::<MutComputer>(rest),
+        "difftest" => difftest_command(rest),
+        "disasm" => disasm_command(rest),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        c => Err(CliError::Usage(format!("unknown command `{}`", c))),
+    }
+}
+
+fn assemble_command(args: &[String]) -> Result<(), CliError> {
+    let mut input = None;
+    let mut output = None;
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "-o" => output = Some(option_value(&mut it, "-o")?),
+            _ => positional(&mut input, arg)?,
+        }
+    }
+    let input = input.ok_or_else(|| CliError::Usage(String::from("no input file given")))?;
+    let (bin, _) = assemble_file(&input)?;
+    write_output(output, &write_hack(&bin))
+}
+
+fn run_program_command<C: Computer>(args: &[String]) -> Result<(), CliError> {
+    let mut input = None;
+    let mut cycles: u64 = 1000;
+    let mut ram = (0, 16);
+    let mut screen = None;
+    let mut frame_every: Option<u64> = None;
+    let mut keys = None;
+    let mut trace = None;
+    let mut trace_format = TraceFormat::Text;
+    let mut snapshot = None;
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--cycles" => {
+                let v = option_value(&mut it, "--cycles")?;
+                cycles = v
+                    .parse()
+                    .map_err(|_| CliError::Usage(format!("invalid cycle count `{}`", v)))?;
+            }
+            "--ram" => ram = parse_ram_range(&option_value(&mut it, "--ram")?)?,
+            "--screen" => screen = Some(option_value(&mut it, "--screen")?),
+            "--keys" => keys = Some(option_value(&mut it, "--keys")?),
+            "--trace" => trace = Some(option_value(&mut it, "--trace")?),
+            "--snapshot" => snapshot = Some(option_value(&mut it, "--snapshot")?),
+            "--trace-format" => {
+                trace_format = match option_value(&mut it, "--trace-format")?.as_str() {
+                    "text" => TraceFormat::Text,
+                    "json" => TraceFormat::JsonLines,
+                    f => return Err(CliError::Usage(format!("unknown trace format `{}`", f))),
+                }
+            }
+            "--frame-every" => {
+                let v = option_value(&mut it, "--frame-every")?;
+                match v.parse() {
+                    Ok(n) if n > 0 => frame_every = Some(n),
+                    _ => return Err(CliError::Usage(format!("invalid frame interval `{}`", v))),
+                }
+            }
+            _ => positional(&mut input, arg)?,
+        }
+    }
+    let input = input.ok_or_else(|| CliError::Usage(String::from("no input file given")))?;
+    if let Some(path) = &screen {
+        if !path.ends_with(".pbm") && !path.ends_with(".ppm") {
+            return Err(CliError::Usage(format!(
+                "unknown image format `{}`, expected .pbm or .ppm",
+                path
+            )));
+        }
+    }
+    if frame_every.is_some() && screen.is_none() {
+        return Err(CliError::Usage(String::from(
+            "--frame-every requires --screen",
+        )));
+    }
+    let (mut c, source_map) = load_computer::<C>(&input)?;
+    if let Some(path) = keys {
+        let script = read_file(&path)?;
+        *c.keyboard() = Keyboard::of_script(&script)
+            .map_err(|e| CliError::Failure(format!("{}: {}", path, e)))?;
+    }
+    if let Some(path) = &trace {
+        let out: Box<dyn Write> = if path == "-" {
+            Box::new(io::stdout())
+        } else {
+            let file = fs::File::create(path)
+                .map_err(|e| CliError::Failure(format!("{}: {}", path, e)))?;
+            Box::new(io::BufWriter::new(file))
+        };
+        let mut tracer = Tracer::of(out, trace_format);
+        if let Some(map) = &source_map {
+            tracer = tracer.with_source_map(map.clone());
+        }
+        c.trace(tracer);
+    }
+    // runs in chunks of one frame so that frames land on multiples of n
+    let mut ran = 0;
+    let reason = loop {
+        let chunk = frame_every.unwrap_or(cycles).min(cycles - ran);
+        let (n, reason) = c.run_until_halt(chunk);
+        ran += n;
+        let done = reason != HaltReason::CycleLimit || ran == cycles;
+        // the last frame shows where the run ended, on a multiple of n or not
+        if let (Some(path), Some(every)) = (&screen, frame_every) {
+            if n == every || done {
+                write_screen(&mut c, &frame_path(path, ran))?;
+            }
+        }
+        if done {
+            break reason;
+        }
+    };
+    if let Some(tracer) = c.stop_trace() {
+        tracer.finish().map_err(|e| {
+            CliError::Failure(format!("{}: {}", trace.as_deref().unwrap_or("-"), e))
+        })?;
+    }
+    // with the trace on stdout the summary moves to stderr, so the trace stays parseable
+    let to_stderr = trace.as_deref() == Some("-");
+    let say = |line: String| {
+        if to_stderr {
+            eprintln!("{}", line)
+        } else {
+            println!("{}", line)
+        }
+    };
+    match reason {
+        HaltReason::Halted => say(format!("halted after {} cycles", ran)),
+        _ => say(format!("stopped after {} cycles", ran)),
+    }
+    if let (Some(path), None) = (&screen, frame_every) {
+        write_screen(&mut c, path)?;
+    }
+    if let Some(path) = &snapshot {
+        fs::write(path, c.snapshot().to_bytes())
+            .map_err(|e| CliError::Failure(format!("{}: {}", path, e)))?;
+    }
+    let pc = c.peek_pc();
+    match source_map.as_ref().and_then(|m| m.get(pc as usize)) {
+        Some(loc) => say(format!("PC = {} at {}", pc, loc)),
+        None => say(format!("PC = {}", pc)),
+    }
+    for addr in ram.0..ram.1 {
+        say(format!("RAM[{}] = {}", addr, c.peek_ram(addr)));
+    }
+    Ok(())
+}
+
+// terminals only report presses, so a key counts as held until it stops repeating
+const KEY_HOLD: Duration = Duration::from_millis(150);
+
+fn play_command<C: Computer>(args: &[String]) -> Result<(), CliError> {
+    let mut input = None;
+    let mut cycles_per_frame = 5000;
+    let mut scale = None;
+    let mut half_blocks = false;
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--cycles-per-frame" => {
+                let v = option_value(&mut it, "--cycles-per-frame")?;
+                cycles_per_frame = v
+                    .parse()
+                    .map_err(|_| CliError::Usage(format!("invalid cycle count `{}`", v)))?;
+            }
+            "--scale" => {
+                let v = option_value(&mut it, "--scale")?;
+                match v.parse() {
+                    Ok(n) if n > 0 => scale = Some(n),
+                    _ => return Err(CliError::Usage(format!("invalid scale `{}`", v))),
+                }
+            }
+            "--half-blocks" => half_blocks = true,
+            _ => positional(&mut input, arg)?,
+        }
+    }
+    let input = input.ok_or_else(|| CliError::Usage(String::from("no input file given")))?;
+    let (mut c, _) = load_computer::<C>(&input)?;
+
+    // cells per character: 1x2 for half blocks, 2x4 for Braille
+    let (cell_w, cell_h) = if half_blocks { (1, 2) } else { (2, 4) };
+    let scale = scale.unwrap_or_else(|| {
+        let (rows, cols) = terminal_size().unwrap_or((24, 80));
+        // at most one character wide, for terminals that report no size at all
+        let max = Screen::WIDTH / cell_w;
+        (1..=max)
+            .find(|&s| Screen::WIDTH / (cell_w * s) <= cols && Screen::HEIGHT / (cell_h * s) < rows)
+            .unwrap_or(max)
+    });
+
+    let _raw = RawTerminal::enter()?;
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buf = [0; 64];
+        while let Ok(n) = io::stdin().read(&mut buf) {
+            if n == 0 || tx.send(buf[..n].to_vec()).is_err() {
+                break;
+            }
+        }
+    });
+    let mut pressed_at = Instant::now();
+    let mut stdout = io::stdout();
+    loop {
+        let bytes: Vec<u8> = rx.try_iter().flatten().collect();
+        // raw mode swallows Ctrl-C
+        if bytes.contains(&0x03) {
+            return Ok(());
+        }
+        if let Some(&key) = decode_keys(&bytes).last() {
+            c.keyboard().press(key);
+            pressed_at = Instant::now();
+        } else if pressed_at.elapsed() > KEY_HOLD {
+            c.keyboard().press(0);
+        }
+        for _ in 0..cycles_per_frame {
+            c.tick(&());
+        }
+        let screen = c.screen();
+        let text = if half_blocks {
+            screen.to_half_blocks(scale)
+        } else {
+            screen.to_braille(scale)
+        };
+        let frame = format!("\x1b[H{}", text.replace('\n', "\r\n"));
+        stdout
+            .write_all(frame.as_bytes())
+            .and_then(|_| stdout.flush())
+            .map_err(|e| CliError::Failure(format!("stdout: {}", e)))?;
+    }
+}
+
+// puts the terminal in raw mode until dropped
+struct RawTerminal(String);
+
+impl RawTerminal {
+    fn enter() -> Result<Self, CliError> {
+        let saved = stty(&["-g"])
+            .ok_or_else(|| CliError::Failure(String::from("stdin is not a terminal")))?;
+        stty(&["raw", "-echo"]);
+        // clear and hide the cursor
+        print!("\x1b[2J\x1b[?25l");
+        Ok(RawTerminal(saved.trim().to_string()))
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        stty(&[&self.0]);
+        println!("\x1b[?25h");
+    }
+}
+
+fn stty(args: &[&str]) -> Option<String> {
+    let out = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()
+        .ok()?;
+    if out.status.success() {
+        String::from_utf8(out.stdout).ok()
+    } else {
+        None
+    }
+}
+
+// (rows, columns)
+fn terminal_size() -> Option<(usize, usize)> {
+    let size = stty(&["size"])?;
+    let (rows, cols) = size.trim().split_once(' ')?;
+    Some((rows.parse().ok()?, cols.parse().ok()?))
+}
+
+fn debug_command<C: Computer>(args: &[String]) -> Result<(), CliError> {
+    let mut input = None;
+    let mut keys = None;
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--keys" => keys = Some(option_value(&mut it, "--keys")?),
+            _ => positional(&mut input, arg)?,
+        }
+    }
+    let input = input.ok_or_else(|| CliError::Usage(String::from("no input file given")))?;
+    let (mut c, source_map) = load_computer::<C>(&input)?;
+    if let Some(path) = keys {
+        let script = read_file(&path)?;
+        *c.keyboard() = Keyboard::of_script(&script)
+            .map_err(|e| CliError::Failure(format!("{}: {}", path, e)))?;
+    }
+    let mut debugger = Debugger::of(c, source_map);
+    println!("{}", debugger.execute("list").unwrap_or_default());
+    let stdin = io::stdin();
+    loop {
+        print!("(hack) ");
+        io::stdout().flush().ok();
+        let mut line = String::new();
+        match stdin.read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) => (),
+            Err(e) => return Err(CliError::Failure(format!("stdin: {}", e))),
+        }
+        match line.trim() {
+            "quit" | "q" => break,
+            _ => match debugger.execute(&line) {
+                Ok(out) if out.is_empty() => (),
+                Ok(out) => println!("{}", out),
+                Err(e) => println!("error: {}", e),
+            },
+        }
+    }
+    Ok(())
+}
+
+fn profile_command<C: Computer>(args: &[String]) -> Result<(), CliError> {
+    let mut input = None;
+    let mut cycles: u64 = 1_000_000;
+    let mut keys = None;
+    let mut top: usize = 20;
+    let mut folded = None;
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--cycles" => cycles = parse_option(&mut it, "--cycles")?,
+            "--keys" => keys = Some(option_value(&mut it, "--keys")?),
+            "--top" => top = parse_option(&mut it, "--top")?,
+            "--folded" => folded = Some(option_value(&mut it, "--folded")?),
+            _ => positional(&mut input, arg)?,
+        }
+    }
+    let input = input.ok_or_else(|| CliError::Usage(String::from("no input file given")))?;
+    let (mut c, source_map) = load_computer::<C>(&input)?;
+    if let Some(path) = keys {
+        let script = read_file(&path)?;
+        *c.keyboard() = Keyboard::of_script(&script)
+            .map_err(|e| CliError::Failure(format!("{}: {}", path, e)))?;
+    }
+    let mut profiler = Profiler::of(source_map);
+    let (ran, reason) = profiler.run(&mut c, cycles);
+    match reason {
+        HaltReason::Halted => println!("halted after {} cycles", ran),
+        _ => println!("stopped after {} cycles", ran),
+    }
+    println!();
+    print!("{}", profiler.report(top));
+    if let Some(path) = folded {
+        fs::write(&path, profiler.folded())
+            .map_err(|e| CliError::Failure(format!("{}: {}", path, e)))?;
+    }
+    Ok(())
+}
+
+// runs the gate-level CPU against the reference model, on a program or on random ones
+fn difftest_command(args: &[String]) -> Result<(), CliError> {
+    let mut input = None;
+    let mut fuzzing = false;
+    let mut seed: u64 = 1;
+    let mut runs: u64 = 100;
+    let mut length: usize = 64;
+    let mut cycles: u64 = 1000;
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--fuzz" => fuzzing = true,
+            "--seed" => seed = parse_option(&mut it, "--seed")?,
+            "--runs" => runs = parse_option(&mut it, "--runs")?,
+            "--length" => length = parse_option(&mut it, "--length")?,
+            "--cycles" => cycles = parse_option(&mut it, "--cycles")?,
+            _ => positional(&mut input, arg)?,
+        }
+    }
+    match (input, fuzzing) {
+        (Some(_), true) => Err(CliError::Usage(String::from("--fuzz takes no input file"))),
+        (None, false) => Err(CliError::Usage(String::from("no input file given"))),
+        (None, true) => {
+            for s in seed..seed + runs {
+                fuzz(s, length, cycles)
+                    .map_err(|d| CliError::Failure(format!("seed {}: {}", s, d)))?;
+            }
+            println!("{} random programs agreed for {} cycles each", runs, cycles);
+            Ok(())
+        }
+        (Some(input), false) => {
+            let (bin, _) = load_program(&input)?;
+            let program = bin
+                .iter()
+                .enumerate()
+                .map(|(i, w)| {
+                    Instruction::decode(w)
+                        .map_err(|e| CliError::Failure(format!("{}: word {}: {}", input, i, e)))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            lockstep(&program, cycles, |_| false)
+                .map_err(|d| CliError::Failure(format!("{}: {}", input, d)))?;
+            println!("agreed for {} cycles", cycles);
+            Ok(())
+        }
+    }
+}
+
+fn disasm_command(args: &[String]) -> Result<(), CliError> {
+    let mut input = None;
+    let mut output = None;
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "-o" => output = Some(option_value(&mut it, "-o")?),
+            _ => positional(&mut input, arg)?,
+        }
+    }
+    let input = input.ok_or_else(|| CliError::Usage(String::from("no input file given")))?;
+    let bin = read_hack_file(&input)?;
+    let code = disassemble(&bin).map_err(|e| CliError::Failure(format!("{}: {}", input, e)))?;
+    write_output(output, &code)
+}
+
+fn option_value<'a>(
+    it: &mut impl Iterator<Item = &'a String>,
+    name: &str,
+) -> Result<String, CliError> {
+    it.next()
+        .cloned()
+        .ok_or_else(|| CliError::Usage(format!("{} requires a value", name)))
+}
+
+fn parse_option<'a, T: std::str::FromStr>(
+    it: &mut impl Iterator<Item = &'a String>,
+    name: &str,
+) -> Result<T, CliError> {
+    let v = option_value(it, name)?;
+    v.parse()
+        .map_err(|_| CliError::Usage(format!("invalid value `{}` for {}", v, name)))
+}
+
+fn positional(slot: &mut Option<String>, arg: &str) -> Result<(), CliError> {
+    if arg.starts_with('-') {
+        return Err(CliError::Usage(format!("unknown option `{}`", arg)));
+    }
+    if slot.is_some() {
+        return Err(CliError::Usage(format!("unexpected argument `{}`", arg)));
+    }
+    *slot = Some(arg.to_string());
+    Ok(())
+}
+
+fn parse_ram_range(s: &str) -> Result<(u16, u16), CliError> {
+    let err = || CliError::Usage(format!("invalid RAM range `{}`", s));
+    let parse = |x: &str| x.parse::<u16>().map_err(|_| err());
+    let (from, to) = match s.split_once("..") {
+        Some((from, to)) => (parse(from)?, parse(to)?),
+        None => {
+            let a = parse(s)?;
+            (a, a.checked_add(1).ok_or_else(err)?)
+        }
+    };
+    if to < from {
+        return Err(err());
+    }
+    Ok((from, to))
+}
+
+fn write_output(path: Option<String>, text: &str) -> Result<(), CliError> {
+    match path {
+        Some(path) => {
+            fs::write(&path, text).map_err(|e| CliError::Failure(format!("{}: {}", path, e)))
+        }
+        None => {
+            print!("{}", text);
+            Ok(())
+        }
+    }
+}
+
+// the image format follows the extension, which is .pbm or .ppm
+fn write_screen<C: Computer>(c: &mut C, path: &str) -> Result<(), CliError> {
+    let screen = c.screen();
+    let image = if path.ends_with(".ppm") {
+        screen.to_ppm()
+    } else {
+        screen.to_pbm()
+    };
+    fs::write(path, image).map_err(|e| CliError::Failure(format!("{}: {}", path, e)))
+}
+
+// out.pbm -> out-000100.pbm for the frame at cycle 100
+fn frame_path(path: &str, cycle: u64) -> String {
+    let (stem, ext) = match path.rfind('.') {
+        Some(i) if !path[i..].contains('/') => path.split_at(i),
+        _ => (path, ""),
+    };
+    format!("{}-{:06}{}", stem, cycle, ext)
+}
+
+fn read_file(path: &str) -> Result<String, CliError> {
+    fs::read_to_string(path).map_err(|e| CliError::Failure(format!("{}: {}", path, e)))
+}
+
+fn assemble_file(path: &str) -> Result<(Vec<Bus16>, SourceMap), CliError> {
+    let code = read_file(path)?;
+    assemble_with_source_map(&code).map_err(|errors| {
+        let msgs: Vec<String> = errors.iter().map(|e| format!("{}:{}", path, e)).collect();
+        CliError::Failure(msgs.join("\n"))
+    })
+}
+
+fn read_hack_file(path: &str) -> Result<Vec<Bus16>, CliError> {
+    let text = read_file(path)?;
+    read_hack(&text).map_err(|e| CliError::Failure(format!("{}: {}", path, e)))
+}
+
+// a snapshot resumes where it was taken; a program starts afresh
+fn load_computer<C: Computer>(path: &str) -> Result<(C, Option<SourceMap>), CliError> {
+    if path.ends_with(".snap") {
+        let bytes = fs::read(path).map_err(|e| CliError::Failure(format!("{}: {}", path, e)))?;
+        let snapshot = Snapshot::of_bytes(&bytes)
+            .map_err(|e| CliError::Failure(format!("{}: {}", path, e)))?;
+        return Ok((C::of_snapshot(&snapshot), None));
+    }
+    let (bin, source_map) = load_program(path)?;
+    let c = C::of(&bin).map_err(|e| CliError::Failure(format!("{}: {}", path, e)))?;
+    Ok((c, source_map))
+}
+
+fn load_program(path: &str) -> Result<(Vec<Bus16>, Option<SourceMap>), CliError> {
+    if path.ends_with(".hack") {
+        Ok((read_hack_file(path)?, None))
+    } else {
+        let (bin, source_map) = assemble_file(path)?;
+        Ok((bin, Some(source_map)))
+    }
 }
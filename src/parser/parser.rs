@@ -208,8 +208,10 @@ impl<'a, Atom, T> MustParser<'a, Atom, T> {
     }
 }
 
+type AtomFn<'t, Atom, R> = Box<dyn 't + Fn(&Atom) -> Option<R>>;
+
 pub struct FuncParser<'t, Atom, R> {
-    func: Box<dyn 't + Fn(&Atom) -> Option<R>>,
+    func: AtomFn<'t, Atom, R>,
 }
 
 impl<'t, Atom, R> Parser<Atom, R> for FuncParser<'t, Atom, R> {
@@ -326,6 +328,7 @@ impl<Atom> NoneParser<Atom> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::assert_matches;
 
     #[test]
     fn vec_parser_works() {
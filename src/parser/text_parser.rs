@@ -20,13 +20,13 @@ pub fn numeric_parser() -> RefinedParser<'static, char, (u32, Vec<u32>), i32> {
             FuncParser::new(|c: &char| c.to_digit(10)),
             RepeatParser::new(FuncParser::new(|c: &char| c.to_digit(10))),
         ),
+        // saturates so that a huge literal still reads as out of range
         |(x, ys)| {
-            let mut result = x;
-            for y in ys.iter() {
-                result *= 10;
-                result += y;
+            let mut result = x as i32;
+            for &y in ys.iter() {
+                result = result.saturating_mul(10).saturating_add(y as i32);
             }
-            result as i32
+            result
         },
     )
 }
@@ -53,4 +53,10 @@ mod tests {
         assert_eq!(numeric_parser().parse(&text, 0), Some((12345, 5)));
         assert_eq!(numeric_parser().parse(&text, 1), Some((2345, 5)));
     }
+
+    #[test]
+    fn numeric_parser_saturates() {
+        let text = "4294967297".chars().collect::<Vec<_>>();
+        assert_eq!(numeric_parser().parse(&text, 0), Some((i32::MAX, 10)));
+    }
 }
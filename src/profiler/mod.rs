@@ -0,0 +1,591 @@
+use std::collections::HashMap;
+
+use crate::assembly::SourceMap;
+use crate::computer::{Computer, HaltReason, TraceEntry};
+use crate::gates::bus16::make_bus16;
+use crate::instruction::Instruction;
+
+// code before the first label, and the frame a program starts in
+const TOP: &str = "(top)";
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HotLoop {
+    // a backward jump from `to` back to `from`
+    pub from: u16,
+    pub to: u16,
+    pub label: String,
+    pub iterations: u64,
+    // cycles spent on from..=to, including loops nested in it
+    pub cycles: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FunctionProfile {
+    pub name: String,
+    pub calls: u64,
+    // cycles spent in the function's own frame
+    pub self_cycles: u64,
+    // self cycles plus those of everything it called
+    pub inclusive_cycles: u64,
+}
+
+// a call path; children are keyed by function
+struct Frame {
+    parent: Option<usize>,
+    function: usize,
+    children: HashMap<usize, usize>,
+}
+
+struct Active {
+    frame: usize,
+    // the address after the jump that made the call
+    return_to: Option<u16>,
+}
+
+// counts what a run spends its cycles on.
+// labels are grouped as the VM translator names them: `Foo.bar$LOOP` belongs to `Foo.bar`,
+// and a jump to a label like `Foo.bar` is a call that returns to the address after the jump.
+// a jump to a function entry is always a call; return addresses are labels like `Foo.bar$ret.0`.
+pub struct Profiler {
+    source_map: Option<SourceMap>,
+    names: Vec<String>,
+    // ROM address -> enclosing label
+    label_at: Vec<usize>,
+    // ROM address -> function starting there
+    entries: HashMap<u16, usize>,
+    counts: Vec<u64>,
+    words: Vec<u16>,
+    total: u64,
+    last_pc: Option<u16>,
+    // (loop start, backward jump) -> times the jump was taken
+    loops: HashMap<(u16, u16), u64>,
+    // (caller, callee) -> calls
+    calls: HashMap<(usize, usize), u64>,
+    frames: Vec<Frame>,
+    stack: Vec<Active>,
+    // (frame, label) -> cycles
+    samples: HashMap<(usize, usize), u64>,
+}
+
+impl Profiler {
+    pub fn of(source_map: Option<SourceMap>) -> Self {
+        let mut names = vec![String::from(TOP)];
+        let mut index: HashMap<String, usize> = HashMap::new();
+        let mut intern = |name: &str| {
+            let name = function_name(name);
+            *index.entry(name.to_string()).or_insert_with(|| {
+                names.push(name.to_string());
+                names.len() - 1
+            })
+        };
+        let mut label_at = vec![0; 0x8000];
+        let mut entries = HashMap::new();
+        if let Some(map) = &source_map {
+            for (addr, slot) in label_at.iter_mut().enumerate().take(map.len()) {
+                if let Some((label, _)) = map.get(addr).and_then(|l| l.label.as_ref()) {
+                    *slot = intern(label);
+                }
+            }
+            for (label, addr) in map.labels() {
+                if is_function(label) && addr < 0x8000 {
+                    entries.insert(addr as u16, intern(label));
+                }
+            }
+        }
+        Profiler {
+            source_map,
+            names,
+            label_at,
+            entries,
+            counts: vec![0; 0x8000],
+            words: vec![0; 0x8000],
+            total: 0,
+            last_pc: None,
+            loops: HashMap::new(),
+            calls: HashMap::new(),
+            frames: vec![Frame {
+                parent: None,
+                function: 0,
+                children: HashMap::new(),
+            }],
+            stack: vec![Active {
+                frame: 0,
+                return_to: None,
+            }],
+            samples: HashMap::new(),
+        }
+    }
+
+    // like Computer::run_until_halt, recording every cycle
+    pub fn run<C: Computer>(&mut self, c: &mut C, max_cycles: u64) -> (u64, HaltReason) {
+        for cycle in 0..max_cycles {
+            let pc = c.peek_pc();
+            if cycle > 0 && c.breakpoints().any(|b| b == pc) {
+                return (cycle, HaltReason::Breakpoint);
+            }
+            if c.is_halted() {
+                return (cycle, HaltReason::Halted);
+            }
+            self.record(&c.step());
+        }
+        (max_cycles, HaltReason::CycleLimit)
+    }
+
+    pub fn record(&mut self, e: &TraceEntry) {
+        let pc = e.pc & 0x7fff;
+        if let Some(prev) = self.last_pc {
+            if pc != prev.wrapping_add(1) {
+                self.jump(prev, pc);
+            }
+        }
+        self.last_pc = Some(pc);
+        self.counts[pc as usize] += 1;
+        self.words[pc as usize] = e.instruction;
+        self.total += 1;
+        let frame = self.stack.last().unwrap().frame;
+        *self
+            .samples
+            .entry((frame, self.label_at[pc as usize]))
+            .or_insert(0) += 1;
+    }
+
+    // calls and returns are not loops even when they jump backward
+    fn jump(&mut self, from: u16, to: u16) {
+        let callee = match self.entries.get(&to) {
+            Some(&f) => f,
+            None => {
+                // a return may skip frames, e.g. after a jump that looked like a call but was not
+                if let Some(i) = self.stack.iter().rposition(|a| a.return_to == Some(to)) {
+                    self.stack.truncate(i);
+                } else if to <= from {
+                    *self.loops.entry((to, from)).or_insert(0) += 1;
+                }
+                return;
+            }
+        };
+        let caller = self.stack.last().unwrap().frame;
+        *self
+            .calls
+            .entry((self.frames[caller].function, callee))
+            .or_insert(0) += 1;
+        let frame = match self.frames[caller].children.get(&callee) {
+            Some(&f) => f,
+            None => {
+                self.frames.push(Frame {
+                    parent: Some(caller),
+                    function: callee,
+                    children: HashMap::new(),
+                });
+                let f = self.frames.len() - 1;
+                self.frames[caller].children.insert(callee, f);
+                f
+            }
+        };
+        self.stack.push(Active {
+            frame,
+            return_to: Some(from.wrapping_add(1)),
+        });
+    }
+
+    pub fn total_cycles(&self) -> u64 {
+        self.total
+    }
+
+    // how many times the instruction at a ROM address ran
+    pub fn count(&self, addr: u16) -> u64 {
+        self.counts[(addr & 0x7fff) as usize]
+    }
+
+    // cycles by enclosing label, most first
+    pub fn labels(&self) -> Vec<(&str, u64)> {
+        let mut cycles = vec![0; self.names.len()];
+        for (&(_, label), &n) in &self.samples {
+            cycles[label] += n;
+        }
+        let mut labels: Vec<(&str, u64)> = cycles
+            .iter()
+            .enumerate()
+            .filter(|&(_, &n)| n > 0)
+            .map(|(i, &n)| (self.names[i].as_str(), n))
+            .collect();
+        labels.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        labels
+    }
+
+    // most cycles first
+    pub fn hot_loops(&self) -> Vec<HotLoop> {
+        let mut loops: Vec<HotLoop> = self
+            .loops
+            .iter()
+            .map(|(&(from, to), &iterations)| HotLoop {
+                from,
+                to,
+                label: self.names[self.label_at[from as usize]].clone(),
+                iterations,
+                cycles: self.counts[from as usize..=to as usize].iter().sum(),
+            })
+            .collect();
+        loops.sort_by(|a, b| {
+            (b.cycles, b.iterations, a.from, a.to).cmp(&(a.cycles, a.iterations, b.from, b.to))
+        });
+        loops
+    }
+
+    // every function that ran or was called, most inclusive cycles first
+    pub fn functions(&self) -> Vec<FunctionProfile> {
+        let mut own = vec![0; self.frames.len()];
+        for (&(frame, _), &n) in &self.samples {
+            own[frame] += n;
+        }
+        // frames are created after their parents
+        let mut subtree = own.clone();
+        for f in (1..self.frames.len()).rev() {
+            subtree[self.frames[f].parent.unwrap()] += subtree[f];
+        }
+        let mut functions: Vec<FunctionProfile> = (0..self.names.len())
+            .map(|f| FunctionProfile {
+                name: self.names[f].clone(),
+                calls: 0,
+                self_cycles: 0,
+                inclusive_cycles: 0,
+            })
+            .collect();
+        for (&(_, callee), &n) in &self.calls {
+            functions[callee].calls += n;
+        }
+        for (i, frame) in self.frames.iter().enumerate() {
+            let f = &mut functions[frame.function];
+            f.self_cycles += own[i];
+            // recursive frames are already counted by the outermost one
+            if !self
+                .ancestors(i)
+                .any(|a| self.frames[a].function == frame.function)
+            {
+                f.inclusive_cycles += subtree[i];
+            }
+        }
+        functions.retain(|f| f.calls > 0 || f.inclusive_cycles > 0);
+        functions.sort_by(|a, b| {
+            b.inclusive_cycles
+                .cmp(&a.inclusive_cycles)
+                .then(a.name.cmp(&b.name))
+        });
+        functions
+    }
+
+    // (caller, callee, calls), most calls first
+    pub fn calls(&self) -> Vec<(&str, &str, u64)> {
+        let mut calls: Vec<(&str, &str, u64)> = self
+            .calls
+            .iter()
+            .map(|(&(caller, callee), &n)| {
+                (self.names[caller].as_str(), self.names[callee].as_str(), n)
+            })
+            .collect();
+        calls.sort_by(|a, b| b.2.cmp(&a.2).then((a.0, a.1).cmp(&(b.0, b.1))));
+        calls
+    }
+
+    fn ancestors(&self, frame: usize) -> impl Iterator<Item = usize> + '_ {
+        std::iter::successors(self.frames[frame].parent, move |&f| self.frames[f].parent)
+    }
+
+    // one `caller;callee;label cycles` line per stack, as flamegraph.pl and inferno read.
+    // the label is left out when it is the function itself.
+    pub fn folded(&self) -> String {
+        let mut lines: Vec<String> = self
+            .samples
+            .iter()
+            .map(|(&(frame, label), &n)| {
+                let mut path: Vec<usize> = self.ancestors(frame).collect();
+                path.reverse();
+                path.push(frame);
+                let mut names: Vec<&str> = path
+                    .iter()
+                    .map(|&f| self.names[self.frames[f].function].as_str())
+                    .collect();
+                if label != self.frames[frame].function {
+                    names.push(&self.names[label]);
+                }
+                format!("{} {}", names.join(";"), n)
+            })
+            .collect();
+        lines.sort();
+        lines.iter().map(|l| l.clone() + "\n").collect()
+    }
+
+    // at most `top` rows per table
+    pub fn report(&self, top: usize) -> String {
+        let percent = |n: u64| 100.0 * n as f64 / self.total.max(1) as f64;
+        let mut out = format!("total cycles: {}\n", self.total);
+
+        out += "\n    cycles       %  label\n";
+        for (label, n) in self.labels().into_iter().take(top) {
+            out += &format!("{:>10} {:>6.1}%  {}\n", n, percent(n), label);
+        }
+
+        let loops = self.hot_loops();
+        if !loops.is_empty() {
+            out += "\n    cycles  iterations  range        label\n";
+            for l in loops.iter().take(top) {
+                out += &format!(
+                    "{:>10} {:>11}  {:<11}  {}\n",
+                    l.cycles,
+                    l.iterations,
+                    format!("{}..{}", l.from, l.to),
+                    l.label
+                );
+            }
+        }
+
+        if !self.calls.is_empty() {
+            out += "\n     calls        self   inclusive       %  function\n";
+            for f in self.functions().iter().take(top) {
+                out += &format!(
+                    "{:>10} {:>11} {:>11} {:>6.1}%  {}\n",
+                    f.calls,
+                    f.self_cycles,
+                    f.inclusive_cycles,
+                    percent(f.inclusive_cycles),
+                    f.name
+                );
+            }
+            out += "\n     calls  caller -> callee\n";
+            for (caller, callee, n) in self.calls().into_iter().take(top) {
+                out += &format!("{:>10}  {} -> {}\n", n, caller, callee);
+            }
+        }
+
+        let mut hot: Vec<usize> = (0..self.counts.len())
+            .filter(|&a| self.counts[a] > 0)
+            .collect();
+        hot.sort_by(|&a, &b| self.counts[b].cmp(&self.counts[a]).then(a.cmp(&b)));
+        out += "\n     count       %  address  instruction\n";
+        for &addr in hot.iter().take(top) {
+            out += &format!(
+                "{:>10} {:>6.1}%  {:>7}  {}\n",
+                self.counts[addr],
+                percent(self.counts[addr]),
+                addr,
+                self.describe(addr)
+            );
+        }
+        out
+    }
+
+    fn describe(&self, addr: usize) -> String {
+        match self.source_map.as_ref().and_then(|m| m.get(addr)) {
+            Some(loc) => loc.to_string(),
+            None => match Instruction::decode(&make_bus16(self.words[addr] as i32)) {
+                Ok(inst) => inst.to_string(),
+                Err(_) => format!("{:016b}", self.words[addr]),
+            },
+        }
+    }
+}
+
+// Foo.bar$LOOP -> Foo.bar
+fn function_name(label: &str) -> &str {
+    label.split('$').next().unwrap_or(label)
+}
+
+// VM functions are named File.function; labels local to them carry a `$`
+fn is_function(label: &str) -> bool {
+    label.contains('.') && !label.contains('$')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembly::assemble_with_source_map;
+    use crate::computer::{FastComputer, MutComputer};
+
+    fn profile<C: Computer>(code: &str) -> Profiler {
+        let (bin, map) = assemble_with_source_map(code).unwrap();
+        let mut c = C::of(&bin).unwrap();
+        let mut p = Profiler::of(Some(map));
+        assert_eq!(p.run(&mut c, 10_000).1, HaltReason::Halted);
+        p
+    }
+
+    // sum = 3 + 2 + 1
+    const LOOP: &str = "@3
+D=A
+@i
+M=D
+(LOOP)
+@i
+D=M
+@sum
+M=D+M
+@i
+MD=M-1
+@LOOP
+D;JGT
+(END)
+@END
+0;JMP
+";
+
+    #[test]
+    fn counts_work() {
+        let p = profile::<MutComputer>(LOOP);
+        assert_eq!(p.total_cycles(), 4 + 3 * 8);
+        assert_eq!(
+            (p.count(0), p.count(4), p.count(11), p.count(12)),
+            (1, 3, 3, 0)
+        );
+        assert_eq!(p.labels(), vec![("LOOP", 24), (TOP, 4)]);
+        assert_eq!(
+            p.hot_loops(),
+            vec![HotLoop {
+                from: 4,
+                to: 11,
+                label: String::from("LOOP"),
+                iterations: 2,
+                cycles: 24,
+            }]
+        );
+        assert!(p.calls().is_empty());
+        assert_eq!(p.folded(), "(top) 4\n(top);LOOP 24\n");
+    }
+
+    // Main.main calls Math.double twice; Math.double's loop runs twice per call
+    const VM: &str = "@Main.main
+0;JMP
+(Math.double)
+@2
+D=A
+(Math.double$LOOP)
+D=D-1
+@Math.double$LOOP
+D;JGT
+@R14
+A=M
+0;JMP
+(Main.main)
+@Main.main$ret.0
+D=A
+@R14
+M=D
+@Math.double
+0;JMP
+(Main.main$ret.0)
+@Main.main$ret.1
+D=A
+@R14
+M=D
+@Math.double
+0;JMP
+(Main.main$ret.1)
+(END)
+@END
+0;JMP
+";
+
+    #[test]
+    fn call_graph_works() {
+        let p = profile::<MutComputer>(VM);
+        // 2 + 12 + 2 * (2 + 6 + 3)
+        assert_eq!(p.total_cycles(), 36);
+        assert_eq!(
+            p.calls(),
+            vec![("Main.main", "Math.double", 2), (TOP, "Main.main", 1)]
+        );
+        assert_eq!(
+            p.functions(),
+            vec![
+                FunctionProfile {
+                    name: String::from(TOP),
+                    calls: 0,
+                    self_cycles: 2,
+                    inclusive_cycles: 36,
+                },
+                FunctionProfile {
+                    name: String::from("Main.main"),
+                    calls: 1,
+                    self_cycles: 12,
+                    inclusive_cycles: 34,
+                },
+                FunctionProfile {
+                    name: String::from("Math.double"),
+                    calls: 2,
+                    self_cycles: 22,
+                    inclusive_cycles: 22,
+                },
+            ]
+        );
+        assert_eq!(
+            p.labels(),
+            vec![("Math.double", 22), ("Main.main", 12), (TOP, 2)]
+        );
+        assert_eq!(
+            p.folded(),
+            "(top) 2\n(top);Main.main 12\n(top);Main.main;Math.double 22\n"
+        );
+        assert_eq!(
+            p.hot_loops(),
+            vec![HotLoop {
+                from: 4,
+                to: 6,
+                label: String::from("Math.double"),
+                iterations: 2,
+                cycles: 12,
+            }]
+        );
+    }
+
+    #[test]
+    fn recursion_is_counted_once() {
+        // Rec.f calls itself until R0 runs out
+        let code = "@3
+D=A
+@R0
+M=D
+@Rec.f
+0;JMP
+(END)
+@END
+0;JMP
+(Rec.f)
+@R0
+MD=M-1
+@Rec.f$DONE
+D;JEQ
+@Rec.f
+0;JMP
+(Rec.f$DONE)
+@END
+0;JMP
+";
+        let p = profile::<FastComputer>(code);
+        let f = p.functions();
+        assert_eq!(f[0].name, TOP);
+        assert_eq!((f[1].name.as_str(), f[1].calls), ("Rec.f", 3));
+        assert_eq!(f[1].inclusive_cycles, p.total_cycles() - 6);
+        assert_eq!(p.calls(), vec![("Rec.f", "Rec.f", 2), (TOP, "Rec.f", 1)]);
+        assert!(p.folded().contains("(top);Rec.f;Rec.f;Rec.f 4\n"));
+    }
+
+    #[test]
+    fn report_works() {
+        let p = profile::<FastComputer>(LOOP);
+        let report = p.report(2);
+        assert!(report.starts_with("total cycles: 28\n"));
+        assert!(report.contains("        24   85.7%  LOOP\n"));
+        assert!(report.contains("        24           2  4..11        LOOP\n"));
+        assert!(report.contains("         3   10.7%        4  line 6 (LOOP): @i\n"));
+        assert!(!report.contains("caller"));
+    }
+
+    #[test]
+    fn works_without_source_map() {
+        let (bin, _) = assemble_with_source_map(LOOP).unwrap();
+        let mut c = FastComputer::of(&bin).unwrap();
+        let mut p = Profiler::of(None);
+        p.run(&mut c, 10_000);
+        assert_eq!(p.labels(), vec![(TOP, 28)]);
+        assert_eq!(p.folded(), "(top) 28\n");
+        assert!(p.report(20).contains("         1    3.6%        0  @3\n"));
+    }
+}
@@ -0,0 +1,182 @@
+// the 512x256 monochrome screen, 32 words per row, leftmost pixel in the lowest bit
+pub struct Screen(Vec<u16>);
+
+impl Screen {
+    pub const WIDTH: usize = 512;
+    pub const HEIGHT: usize = 256;
+    pub const WORDS: usize = Self::WIDTH / 16 * Self::HEIGHT;
+
+    pub fn of(words: Vec<u16>) -> Self {
+        assert_eq!(words.len(), Self::WORDS, "a screen is {} words", Self::WORDS);
+        Screen(words)
+    }
+
+    pub fn words(&self) -> &[u16] {
+        &self.0
+    }
+
+    // true is black
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.0[y * Self::WIDTH / 16 + x / 16] & (1 << (x % 16)) != 0
+    }
+
+    // binary PBM (P4): 1 is black, leftmost pixel in the highest bit
+    pub fn to_pbm(&self) -> Vec<u8> {
+        let mut image = format!("P4\n{} {}\n", Self::WIDTH, Self::HEIGHT).into_bytes();
+        for word in self.0.iter() {
+            let w = word.reverse_bits();
+            image.push((w >> 8) as u8);
+            image.push(w as u8);
+        }
+        image
+    }
+
+    // true if any pixel in the scale x scale block at block coordinates (x, y) is black
+    fn block(&self, x: usize, y: usize, scale: usize) -> bool {
+        let (x0, y0) = (x * scale, y * scale);
+        (y0..(y0 + scale).min(Self::HEIGHT))
+            .any(|y| (x0..(x0 + scale).min(Self::WIDTH)).any(|x| self.pixel(x, y)))
+    }
+
+    // one character per 1x2 blocks of scale x scale pixels
+    pub fn to_half_blocks(&self, scale: usize) -> String {
+        let (w, h) = (Self::WIDTH.div_ceil(scale), Self::HEIGHT.div_ceil(scale));
+        let mut text = String::new();
+        for row in 0..h.div_ceil(2) {
+            for x in 0..w {
+                let top = self.block(x, row * 2, scale);
+                let bottom = row * 2 + 1 < h && self.block(x, row * 2 + 1, scale);
+                text.push(match (top, bottom) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                });
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    // one character per 2x4 blocks of scale x scale pixels
+    pub fn to_braille(&self, scale: usize) -> String {
+        const DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+        let (w, h) = (Self::WIDTH.div_ceil(scale), Self::HEIGHT.div_ceil(scale));
+        let mut text = String::new();
+        for row in 0..h.div_ceil(4) {
+            for col in 0..w.div_ceil(2) {
+                let mut c = 0x2800;
+                for (dy, dots) in DOTS.iter().enumerate() {
+                    for (dx, dot) in dots.iter().enumerate() {
+                        let (x, y) = (col * 2 + dx, row * 4 + dy);
+                        if x < w && y < h && self.block(x, y, scale) {
+                            c |= dot;
+                        }
+                    }
+                }
+                text.push(char::from_u32(c).unwrap());
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    // binary PPM (P6)
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let mut image = format!("P6\n{} {}\n255\n", Self::WIDTH, Self::HEIGHT).into_bytes();
+        for y in 0..Self::HEIGHT {
+            for x in 0..Self::WIDTH {
+                let c = if self.pixel(x, y) { 0 } else { 255 };
+                image.extend_from_slice(&[c, c, c]);
+            }
+        }
+        image
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screen_with(writes: &[(usize, u16)]) -> Screen {
+        let mut words = vec![0; Screen::WORDS];
+        for &(i, w) in writes {
+            words[i] = w;
+        }
+        Screen::of(words)
+    }
+
+    #[test]
+    fn pixel_works() {
+        let s = screen_with(&[(0, 0b101), (33, 0x8000), (Screen::WORDS - 1, 0x8000)]);
+        assert!(s.pixel(0, 0));
+        assert!(!s.pixel(1, 0));
+        assert!(s.pixel(2, 0));
+        assert!(s.pixel(31, 1));
+        assert!(!s.pixel(15, 1));
+        assert!(s.pixel(511, 255));
+        assert!(!s.pixel(510, 255));
+    }
+
+    #[test]
+    fn to_pbm_works() {
+        let s = screen_with(&[(0, 0b1000_0000_0000_0101), (32, 0xff00)]);
+        let image = s.to_pbm();
+        let header = b"P4\n512 256\n";
+        assert_eq!(&image[..header.len()], header);
+        assert_eq!(image.len(), header.len() + 512 / 8 * 256);
+        let body = &image[header.len()..];
+        assert_eq!(body[0..2], [0b1010_0000, 0b0000_0001]);
+        assert_eq!(body[2..64], [0; 62]);
+        assert_eq!(body[64..66], [0x00, 0xff]);
+    }
+
+    #[test]
+    fn to_ppm_works() {
+        let s = screen_with(&[(0, 0b10)]);
+        let image = s.to_ppm();
+        let header = b"P6\n512 256\n255\n";
+        assert_eq!(&image[..header.len()], header);
+        assert_eq!(image.len(), header.len() + 512 * 256 * 3);
+        let body = &image[header.len()..];
+        assert_eq!(body[0..9], [255, 255, 255, 0, 0, 0, 255, 255, 255]);
+    }
+
+    #[test]
+    fn to_half_blocks_works() {
+        // pixels (0, 0), (17, 1) and (511, 255)
+        let s = screen_with(&[(0, 0b1), (33, 0b10), (Screen::WORDS - 1, 0x8000)]);
+        let text = s.to_half_blocks(1);
+        let lines: Vec<Vec<char>> = text.lines().map(|l| l.chars().collect()).collect();
+        assert_eq!(lines.len(), 128);
+        assert!(lines.iter().all(|l| l.len() == 512));
+        assert_eq!(lines[0][0], '▀');
+        assert_eq!(lines[0][17], '▄');
+        assert_eq!(lines[127][511], '▄');
+        assert_eq!(text.chars().filter(|&c| c != ' ' && c != '\n').count(), 3);
+
+        let text = s.to_half_blocks(8);
+        let lines: Vec<Vec<char>> = text.lines().map(|l| l.chars().collect()).collect();
+        assert_eq!((lines.len(), lines[0].len()), (16, 64));
+        assert_eq!(lines[0][0], '▀');
+        assert_eq!(lines[0][2], '▀');
+        assert_eq!(lines[15][63], '▄');
+    }
+
+    #[test]
+    fn to_braille_works() {
+        // pixels (0, 0), (1, 3) and (511, 255)
+        let s = screen_with(&[(0, 0b1), (96, 0b10), (Screen::WORDS - 1, 0x8000)]);
+        let text = s.to_braille(1);
+        let lines: Vec<Vec<char>> = text.lines().map(|l| l.chars().collect()).collect();
+        assert_eq!((lines.len(), lines[0].len()), (64, 256));
+        assert_eq!(lines[0][0], '\u{2881}');
+        assert_eq!(lines[63][255], '\u{2880}');
+        assert_eq!(lines[0][1], '\u{2800}');
+
+        let text = s.to_braille(4);
+        let lines: Vec<Vec<char>> = text.lines().map(|l| l.chars().collect()).collect();
+        assert_eq!((lines.len(), lines[0].len()), (16, 64));
+        assert_eq!(lines[0][0], '\u{2801}');
+    }
+}
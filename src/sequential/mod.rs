@@ -3,8 +3,9 @@ pub use register::{Register16, Register16Input};
 
 mod ram16k;
 mod ram4k;
-pub use ram4k::{MutRam4k, Ram4kInput};
 mod ram512;
 mod ram64;
 mod ram8;
-pub use ram16k::{MutRam16k, Ram16kInput};
+mod ram8k;
+pub use ram16k::{MutRam16k, Ram16k, Ram16kInput};
+pub use ram8k::{MutRam8k, Ram8k, Ram8kInput};
@@ -1,11 +1,15 @@
-use crate::gates::bus16::Bus16;
+use crate::gates::bit;
+use crate::gates::bus16::{mux4way16, Bus16};
+use crate::gates::bus2::Bus2;
 use crate::general::Zero;
-use crate::infrastructure::sequential::MutSC;
+use crate::infrastructure::sequential::{ArraySC4, FeedforwardSC, FeedforwardSCDef, MutSC};
 use crate::primitive::Bit;
 
-use super::ram4k::{MutRam4k, Ram4kInput};
+use super::ram4k::{MutRam4k, Ram4k, Ram4kInput};
 
-pub struct MutRam16k(Box<[MutRam4k; 4]>);
+pub type Ram16k = FeedforwardSC<ArraySC4<Ram4k>, Ram16kImpl>;
+
+pub struct Ram16kImpl;
 
 pub struct Ram16kInput {
     pub input: Bus16,
@@ -13,6 +17,37 @@ pub struct Ram16kInput {
     pub load: Bit,
 }
 
+impl FeedforwardSCDef<ArraySC4<Ram4k>> for Ram16kImpl {
+    type Input = Ram16kInput;
+    type Output = Bus16;
+    type Jump = Bus2;
+
+    fn new() -> ArraySC4<Ram4k> {
+        ArraySC4::new()
+    }
+    fn pre(input: &Self::Input) -> ([Ram4kInput; 4], Self::Jump) {
+        let Ram16kInput {
+            input: i,
+            address: a,
+            load,
+        } = input;
+        let r = |load: Bit| Ram4kInput {
+            input: i.clone(),
+            address: [
+                a[2], a[3], a[4], a[5], a[6], a[7], a[8], a[9], a[10], a[11], a[12], a[13],
+            ],
+            load,
+        };
+        let [l0, l1, l2, l3] = bit::dmux4way(*load, &[a[0], a[1]]);
+        ([r(l0), r(l1), r(l2), r(l3)], [a[0], a[1]])
+    }
+    fn post(b: &[Bus16; 4], jump: &Self::Jump) -> Self::Output {
+        mux4way16(&b[0], &b[1], &b[2], &b[3], jump)
+    }
+}
+
+pub struct MutRam16k(Box<[MutRam4k; 4]>);
+
 impl MutSC for MutRam16k {
     type Input = Ram16kInput;
     type Output = Bus16;
@@ -57,8 +92,40 @@ mod tests {
     use super::*;
     use crate::assert_bit_equals;
     use crate::assert_bus16_equals;
-    use crate::gates::bus16::testing::make_bus16;
+    use crate::gates::bus16::make_bus16;
     use crate::gates::bus3::testing::make_bus3;
+    use crate::infrastructure::sequential::SequentialCircuit;
+
+    #[test]
+    fn ram16k_works() {
+        let mut r = Ram16k::new();
+        // one address in each RAM4K
+        let address = |i: i32| {
+            let b = make_bus16(i * 0x1001);
+            [
+                b[2], b[3], b[4], b[5], b[6], b[7], b[8], b[9], b[10], b[11], b[12], b[13], b[14],
+                b[15],
+            ]
+        };
+        for i in 0..4 {
+            let (o, rr) = r.tick(&Ram16kInput {
+                input: make_bus16(i),
+                address: address(i),
+                load: Bit::Positive,
+            });
+            r = rr;
+            assert_bus16_equals!(o, make_bus16(0), format!("addr = {}, {:?}", i, o));
+        }
+        for i in 0..4 {
+            let (o, rr) = r.tick(&Ram16kInput {
+                input: make_bus16(-1),
+                address: address(i),
+                load: Bit::Negative,
+            });
+            r = rr;
+            assert_bus16_equals!(o, make_bus16(i));
+        }
+    }
 
     #[test]
     fn mut_ram16k_works() {
@@ -120,7 +120,7 @@ impl MutSC for MutRam512 {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::gates::bus16::testing::*;
+    use crate::gates::bus16::make_bus16;
     use crate::gates::bus3::testing::*;
     use crate::infrastructure::sequential::SequentialCircuit;
 
@@ -123,7 +123,7 @@ impl Zero for MutRam64 {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::gates::bus16::testing::*;
+    use crate::gates::bus16::make_bus16;
     use crate::gates::bus3::testing::*;
     use crate::general::Zero;
     use crate::infrastructure::sequential::SequentialCircuit;
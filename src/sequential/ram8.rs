@@ -73,7 +73,7 @@ impl FeedforwardSCDef<ArraySC8<Register16>> for Ram8Impl {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::gates::bus16::testing::*;
+    use crate::gates::bus16::make_bus16;
     use crate::gates::bus3::testing::*;
     use crate::infrastructure::sequential::SequentialCircuit;
 
@@ -115,7 +115,6 @@ mod tests {
 
 pub mod testing {
     use super::Ram8;
-    use crate::infrastructure::sequential::testing;
 
     impl Ram8 {
         pub fn peek_at(&self, i: usize) {
@@ -0,0 +1,127 @@
+use crate::gates::bit;
+use crate::gates::bus16::{mux, Bus16};
+use crate::general::Zero;
+use crate::infrastructure::sequential::{
+    ArrayMSC2, ArraySC2, FeedforwardMSC, FeedforwardMSCDef, FeedforwardSC, FeedforwardSCDef,
+};
+use crate::primitive::Bit;
+
+use super::ram4k::{MutRam4k, Ram4k, Ram4kInput};
+
+// the screen's memory
+pub type Ram8k = FeedforwardSC<ArraySC2<Ram4k>, Ram8kImpl>;
+
+pub struct Ram8kImpl;
+
+pub struct Ram8kInput {
+    pub input: Bus16,
+    pub address: [Bit; 13],
+    pub load: Bit,
+}
+
+impl FeedforwardSCDef<ArraySC2<Ram4k>> for Ram8kImpl {
+    type Input = Ram8kInput;
+    type Output = Bus16;
+    type Jump = Bit;
+
+    fn new() -> ArraySC2<Ram4k> {
+        ArraySC2::new()
+    }
+    fn pre(input: &Self::Input) -> ([Ram4kInput; 2], Self::Jump) {
+        let Ram8kInput {
+            input: i,
+            address: a,
+            load,
+        } = input;
+        let r = |load: Bit| Ram4kInput {
+            input: i.clone(),
+            address: [
+                a[1], a[2], a[3], a[4], a[5], a[6], a[7], a[8], a[9], a[10], a[11], a[12],
+            ],
+            load,
+        };
+        let [l0, l1] = bit::dmux(*load, a[0]);
+        ([r(l0), r(l1)], a[0])
+    }
+    fn post(b: &[Bus16; 2], jump: &Self::Jump) -> Self::Output {
+        mux(&b[0], &b[1], *jump)
+    }
+}
+
+// the same circuit of mutable RAM4Ks
+pub type MutRam8k = FeedforwardMSC<ArrayMSC2<MutRam4k>, Ram8kImpl>;
+
+impl FeedforwardMSCDef<ArrayMSC2<MutRam4k>> for Ram8kImpl {
+    type Input = Ram8kInput;
+    type Output = Bus16;
+    type Jump = Bit;
+
+    fn pre(input: &Self::Input) -> ([Ram4kInput; 2], Self::Jump) {
+        <Self as FeedforwardSCDef<ArraySC2<Ram4k>>>::pre(input)
+    }
+    fn post(b: &[Bus16; 2], jump: &Self::Jump) -> Self::Output {
+        <Self as FeedforwardSCDef<ArraySC2<Ram4k>>>::post(b, jump)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gates::bus16::make_bus16;
+    use crate::infrastructure::sequential::{MutSC, SequentialCircuit};
+
+    use crate::assert_bit_equals;
+    use crate::assert_bus16_equals;
+
+    // one address in each RAM4K
+    fn address(i: i32) -> [Bit; 13] {
+        let b = make_bus16(i * 0x1001);
+        [
+            b[3], b[4], b[5], b[6], b[7], b[8], b[9], b[10], b[11], b[12], b[13], b[14], b[15],
+        ]
+    }
+
+    #[test]
+    fn ram8k_works() {
+        let mut r = Ram8k::new();
+        for i in 0..2 {
+            let (o, rr) = r.tick(&Ram8kInput {
+                input: make_bus16(i + 1),
+                address: address(i),
+                load: Bit::Positive,
+            });
+            r = rr;
+            assert_bus16_equals!(o, make_bus16(0));
+        }
+        for i in 0..2 {
+            let (o, rr) = r.tick(&Ram8kInput {
+                input: make_bus16(-1),
+                address: address(i),
+                load: Bit::Negative,
+            });
+            r = rr;
+            assert_bus16_equals!(o, make_bus16(i + 1));
+        }
+    }
+
+    #[test]
+    fn mut_ram8k_works() {
+        let mut r = MutRam8k::new();
+        for i in 0..2 {
+            let o = r.tick(&Ram8kInput {
+                input: make_bus16(i + 1),
+                address: address(i),
+                load: Bit::Positive,
+            });
+            assert_bus16_equals!(o, make_bus16(0));
+        }
+        for i in 0..2 {
+            let o = r.tick(&Ram8kInput {
+                input: make_bus16(-1),
+                address: address(i),
+                load: Bit::Negative,
+            });
+            assert_bus16_equals!(o, make_bus16(i + 1));
+        }
+    }
+}
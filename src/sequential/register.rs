@@ -79,6 +79,23 @@ impl FeedforwardSCDef<ArraySC16<Register>> for Register16Impl {
     }
 }
 
+// reads and writes the flip-flops directly, without a clock tick
+impl Register16 {
+    pub fn value(&self) -> bus16::Bus16 {
+        let mut b = [Bit::Negative; 16];
+        for (i, x) in b.iter_mut().enumerate() {
+            *x = *self.peek().at(i).peek().state();
+        }
+        b
+    }
+
+    pub fn set(&mut self, value: &bus16::Bus16) {
+        for (i, &x) in value.iter().enumerate() {
+            *self.peek_mut().at_mut(i).peek_mut().state_mut() = x;
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -107,6 +124,41 @@ mod tests {
         assert_bit_equals!(o, n);
     }
 
+    #[test]
+    fn register16_value_works() {
+        let fxt = [
+            Bit::Positive,
+            Bit::Negative,
+            Bit::Negative,
+            Bit::Positive,
+            Bit::Positive,
+            Bit::Negative,
+            Bit::Positive,
+            Bit::Negative,
+            Bit::Negative,
+            Bit::Negative,
+            Bit::Positive,
+            Bit::Positive,
+            Bit::Negative,
+            Bit::Positive,
+            Bit::Negative,
+            Bit::Positive,
+        ];
+        let r = Register16::new();
+        let (_, mut r) = r.tick(&Register16Input {
+            input: fxt.clone(),
+            load: Bit::Positive,
+        });
+        assert_bus16_equals!(&r.value(), &fxt);
+        r.set(&[Bit::Positive; 16]);
+        assert_bus16_equals!(&r.value(), &[Bit::Positive; 16]);
+        let (o, _) = r.tick(&Register16Input {
+            input: fxt,
+            load: Bit::Negative,
+        });
+        assert_bus16_equals!(&o, &[Bit::Positive; 16]);
+    }
+
     #[test]
     fn register16_works() {
         let (p, n) = (Bit::Positive, Bit::Negative);
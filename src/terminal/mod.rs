@@ -0,0 +1,90 @@
+use crate::keyboard::*;
+
+// turns bytes read from a raw-mode terminal into Hack key codes
+pub fn decode_keys(bytes: &[u8]) -> Vec<u16> {
+    let mut keys = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let (key, n) = match bytes[i] {
+            0x1b => decode_escape(&bytes[i + 1..]),
+            b'\r' | b'\n' => (Some(NEWLINE), 1),
+            0x7f | 0x08 => (Some(BACKSPACE), 1),
+            c @ 0x20..=0x7e => (Some(c as u16), 1),
+            _ => (None, 1),
+        };
+        keys.extend(key);
+        i += n;
+    }
+    keys
+}
+
+// returns the key and the length of the sequence including the escape
+fn decode_escape(rest: &[u8]) -> (Option<u16>, usize) {
+    match rest {
+        [b'[', b'D', ..] => (Some(LEFT), 3),
+        [b'[', b'A', ..] => (Some(UP), 3),
+        [b'[', b'C', ..] => (Some(RIGHT), 3),
+        [b'[', b'B', ..] => (Some(DOWN), 3),
+        [b'[', b'H', ..] | [b'O', b'H', ..] => (Some(HOME), 3),
+        [b'[', b'F', ..] | [b'O', b'F', ..] => (Some(END), 3),
+        [b'O', c @ b'P'..=b'S', ..] => (Some(F1 + (c - b'P') as u16), 3),
+        [b'[', ..] => {
+            // CSI sequences such as "\x1b[5~" end with a byte in 0x40..=0x7e
+            match rest[1..].iter().position(|c| (0x40..=0x7e).contains(c)) {
+                Some(p) => (csi_key(&rest[1..p + 1], rest[p + 1]), p + 3),
+                None => (None, rest.len() + 1),
+            }
+        }
+        _ => (Some(ESC), 1),
+    }
+}
+
+fn csi_key(params: &[u8], last: u8) -> Option<u16> {
+    if last != b'~' {
+        return None;
+    }
+    match params {
+        b"1" | b"7" => Some(HOME),
+        b"4" | b"8" => Some(END),
+        b"5" => Some(PAGE_UP),
+        b"6" => Some(PAGE_DOWN),
+        b"2" => Some(INSERT),
+        b"3" => Some(DELETE),
+        b"15" => Some(F1 + 4),
+        b"17" => Some(F1 + 5),
+        b"18" => Some(F1 + 6),
+        b"19" => Some(F1 + 7),
+        b"20" => Some(F1 + 8),
+        b"21" => Some(F1 + 9),
+        b"23" => Some(F1 + 10),
+        b"24" => Some(F1 + 11),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_keys_works() {
+        assert_eq!(decode_keys(b"aZ 9"), vec![97, 90, 32, 57]);
+        assert_eq!(decode_keys(b"\r\x7f"), vec![128, 129]);
+        assert_eq!(
+            decode_keys(b"\x1b[D\x1b[A\x1b[C\x1b[B"),
+            vec![130, 131, 132, 133]
+        );
+        assert_eq!(
+            decode_keys(b"\x1b[H\x1b[F\x1b[5~\x1b[6~\x1b[2~\x1b[3~"),
+            vec![134, 135, 136, 137, 138, 139]
+        );
+        assert_eq!(decode_keys(b"\x1bOP\x1b[24~"), vec![141, 152]);
+        assert_eq!(decode_keys(b"\x1b"), vec![140]);
+    }
+
+    #[test]
+    fn decode_keys_skips_unknown_input() {
+        assert_eq!(decode_keys(b"\x01a\x1b[1;5Cb\x1b[99~c"), vec![97, 98, 99]);
+        assert_eq!(decode_keys(b"a\x1b[12"), vec![97]);
+    }
+}